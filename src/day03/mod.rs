@@ -1,9 +1,7 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, fmt};
 
-use itertools::Itertools;
-
-fn charset(s: &str) -> HashSet<char> {
-    s.chars().collect()
+fn charset(s: &[char]) -> HashSet<char> {
+    s.iter().copied().collect()
 }
 
 fn prioritize(c: char) -> Option<i32> {
@@ -14,33 +12,208 @@ fn prioritize(c: char) -> Option<i32> {
     }
 }
 
-pub fn puzzle1(input: &str) -> i32 {
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    OddLength {
+        line: usize,
+        len: usize,
+    },
+    InvalidItem {
+        line: usize,
+        column: usize,
+        item: char,
+    },
+    IncompleteGroup {
+        line: usize,
+        len: usize,
+        size: usize,
+    },
+    EmptyGroup,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::OddLength { line, len } => {
+                write!(f, "line {line}: odd number of items ({len})")
+            }
+            Error::InvalidItem { line, column, item } => {
+                write!(f, "line {line}, column {column}: invalid item {item:?}")
+            }
+            Error::IncompleteGroup { line, len, size } => {
+                write!(f, "line {line}: group has {len} rucksacks, expected {size}")
+            }
+            Error::EmptyGroup => write!(f, "groups need at least one rucksack"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Item {
+    pub item: char,
+    pub priority: i32,
+    // one list of one-based columns for each compartment or rucksack
+    pub positions: Vec<Vec<usize>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Warning {
+    NoneShared,
+    ManyShared,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    // one-based line numbers
+    pub lines: Vec<usize>,
+    pub items: Vec<Item>,
+}
+
+impl Report {
+    pub fn priority(&self) -> i32 {
+        self.items.iter().map(|item| item.priority).sum()
+    }
+
+    pub fn warning(&self) -> Option<Warning> {
+        match self.items.len() {
+            0 => Some(Warning::NoneShared),
+            1 => None,
+            _ => Some(Warning::ManyShared),
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self.lines.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+        let label = if lines.len() == 1 { "line" } else { "lines" };
+        write!(
+            f,
+            "{label} {}: priority {}",
+            lines.join(","),
+            self.priority()
+        )?;
+        for Item {
+            item,
+            priority,
+            positions,
+        } in &self.items
+        {
+            write!(f, "; {item} ({priority}) at {positions:?}")?;
+        }
+        match self.warning() {
+            Some(Warning::NoneShared) => write!(f, "; warning: no shared item"),
+            Some(Warning::ManyShared) => write!(f, "; warning: multiple shared items"),
+            None => Ok(()),
+        }
+    }
+}
+
+// `line` and the reported column are both one-based
+fn check(line: usize, chars: &[char]) -> Result<(), Error> {
+    match chars.iter().position(|&c| prioritize(c).is_none()) {
+        Some(column) => Err(Error::InvalidItem {
+            line,
+            column: column + 1,
+            item: chars[column],
+        }),
+        None => Ok(()),
+    }
+}
+
+// each part is a slice of items along with the column where it starts in its line
+fn report(lines: Vec<usize>, parts: &[(usize, &[char])]) -> Report {
+    let mut shared = charset(parts[0].1);
+    for (_, part) in &parts[1..] {
+        shared = shared.intersection(&charset(part)).copied().collect();
+    }
+    let mut shared = shared.into_iter().collect::<Vec<_>>();
+    shared.sort_by_key(|&c| prioritize(c));
+    let items = shared
+        .into_iter()
+        .map(|item| Item {
+            item,
+            priority: prioritize(item).unwrap(),
+            positions: parts
+                .iter()
+                .map(|&(offset, part)| {
+                    (0..part.len())
+                        .filter(|&i| part[i] == item)
+                        .map(|i| offset + i + 1)
+                        .collect()
+                })
+                .collect(),
+        })
+        .collect();
+    Report { lines, items }
+}
+
+pub fn rucksacks(input: &str) -> Vec<Result<Report, Error>> {
     input
         .lines()
-        .map(|line| {
-            let n = line.len() / 2;
-            charset(&line[..n])
-                .intersection(&charset(&line[n..]))
-                .map(|c| prioritize(*c).unwrap())
-                .sum::<i32>()
+        .enumerate()
+        .map(|(i, line)| {
+            let chars = line.chars().collect::<Vec<_>>();
+            if chars.len() % 2 != 0 {
+                return Err(Error::OddLength {
+                    line: i + 1,
+                    len: chars.len(),
+                });
+            }
+            check(i + 1, &chars)?;
+            let n = chars.len() / 2;
+            Ok(report(vec![i + 1], &[(0, &chars[..n]), (n, &chars[n..])]))
+        })
+        .collect()
+}
+
+pub fn groups(input: &str, size: usize) -> Vec<Result<Report, Error>> {
+    if size == 0 {
+        return vec![Err(Error::EmptyGroup)];
+    }
+    let lines = input.lines().collect::<Vec<_>>();
+    lines
+        .chunks(size)
+        .enumerate()
+        .map(|(g, group)| {
+            let first = g * size + 1;
+            if group.len() < size {
+                return Err(Error::IncompleteGroup {
+                    line: first,
+                    len: group.len(),
+                    size,
+                });
+            }
+            let mut parts = vec![];
+            for (i, line) in group.iter().enumerate() {
+                let chars = line.chars().collect::<Vec<_>>();
+                check(first + i, &chars)?;
+                parts.push(chars);
+            }
+            let parts = parts.iter().map(|p| (0, &p[..])).collect::<Vec<_>>();
+            Ok(report((first..first + size).collect(), &parts))
+        })
+        .collect()
+}
+
+// the puzzles expect exactly one shared item, so anything else is an error rather than summed
+fn total(reports: Vec<Result<Report, Error>>) -> i32 {
+    reports
+        .into_iter()
+        .map(|report| match report {
+            Ok(report) if report.warning().is_some() => panic!("{report}"),
+            Ok(report) => report.priority(),
+            Err(err) => panic!("{err}"),
         })
         .sum()
 }
 
+pub fn puzzle1(input: &str) -> i32 {
+    total(rucksacks(input))
+}
+
 pub fn puzzle2(input: &str) -> i32 {
-    let mut sum = 0;
-    for mut group in &input.lines().chunks(3) {
-        let first = group.next().unwrap();
-        let mut chars = charset(first);
-        for line in group {
-            chars = chars.intersection(&charset(line)).copied().collect();
-        }
-        sum += chars
-            .into_iter()
-            .map(|c| prioritize(c).unwrap())
-            .sum::<i32>();
-    }
-    sum
+    total(groups(input, 3))
 }
 
 #[cfg(test)]
@@ -69,4 +242,63 @@ mod tests {
     fn test_puzzle2_input() {
         assert_eq!(puzzle2(INPUT), 2577);
     }
+
+    #[test]
+    fn test_rucksacks_example() {
+        let first = rucksacks(EXAMPLE).remove(0).unwrap();
+        assert_eq!(
+            first.items,
+            vec![Item {
+                item: 'p',
+                priority: 16,
+                positions: vec![vec![5], vec![24]],
+            }],
+        );
+        assert_eq!(first.warning(), None);
+    }
+
+    #[test]
+    fn test_groups_size() {
+        assert_eq!(groups(EXAMPLE, 2).len(), 3);
+        assert_eq!(
+            groups(EXAMPLE, 4)[1],
+            Err(Error::IncompleteGroup {
+                line: 5,
+                len: 2,
+                size: 4,
+            }),
+        );
+        assert_eq!(groups(EXAMPLE, 0), vec![Err(Error::EmptyGroup)]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            rucksacks("abc\na1a1\nabcd"),
+            vec![
+                Err(Error::OddLength { line: 1, len: 3 }),
+                Err(Error::InvalidItem {
+                    line: 2,
+                    column: 2,
+                    item: '1',
+                }),
+                Ok(Report {
+                    lines: vec![3],
+                    items: vec![],
+                }),
+            ],
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "warning: multiple shared items")]
+    fn test_total_many_shared() {
+        puzzle1("abab");
+    }
+
+    #[test]
+    #[should_panic(expected = "warning: no shared item")]
+    fn test_total_none_shared() {
+        puzzle1("abcd");
+    }
 }
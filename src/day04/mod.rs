@@ -1,14 +1,10 @@
 use regex::Regex;
 
-#[derive(Clone)]
-struct Range {
-    start: i32,
-    end: i32,
-}
+use crate::interval::{self, Interval};
 
 struct Pair {
-    first: Range,
-    second: Range,
+    first: Interval,
+    second: Interval,
 }
 
 fn parse(input: &str) -> Vec<Pair> {
@@ -17,43 +13,111 @@ fn parse(input: &str) -> Vec<Pair> {
         .lines()
         .map(|line| {
             let cap = re.captures(line).unwrap();
+            // an assignment written backwards, like `5-3`, still covers the same sections
+            let sections = |i: usize| {
+                let (a, b) = (cap[i].parse().unwrap(), cap[i + 1].parse().unwrap());
+                Interval::new(isize::min(a, b), isize::max(a, b))
+            };
             Pair {
-                first: Range {
-                    start: cap[1].parse().unwrap(),
-                    end: cap[2].parse().unwrap(),
-                },
-                second: Range {
-                    start: cap[3].parse().unwrap(),
-                    end: cap[4].parse().unwrap(),
-                },
+                first: sections(1),
+                second: sections(3),
             }
         })
         .collect()
 }
 
-fn count_pairs(pairs: Vec<Pair>, pred: fn(first: Range, second: Range) -> bool) -> i32 {
+fn count_pairs(pairs: Vec<Pair>, pred: fn(first: Interval, second: Interval) -> bool) -> i32 {
     pairs
         .into_iter()
-        .filter(|Pair { first, second }| {
-            pred(first.clone(), second.clone()) || pred(second.clone(), first.clone())
-        })
+        .filter(|&Pair { first, second }| pred(first, second) || pred(second, first))
         .count() as i32
 }
 
-fn contains(first: Range, second: Range) -> bool {
-    first.start <= second.start && second.end <= first.end
+pub fn puzzle1(input: &str) -> i32 {
+    count_pairs(parse(input), Interval::contains)
 }
 
-pub fn puzzle1(input: &str) -> i32 {
-    count_pairs(parse(input), contains)
+pub fn puzzle2(input: &str) -> i32 {
+    count_pairs(parse(input), Interval::overlaps)
 }
 
-fn left_overlap(first: Range, second: Range) -> bool {
-    first.start <= second.start && second.start <= first.end
+pub fn coverage(input: &str) -> Vec<(Interval, usize)> {
+    interval::coverage(
+        parse(input)
+            .into_iter()
+            .flat_map(|Pair { first, second }| [first, second]),
+    )
 }
 
-pub fn puzzle2(input: &str) -> i32 {
-    count_pairs(parse(input), left_overlap)
+// elves are numbered from 1 in input order, so line `n` holds elves `2n - 1` and `2n`
+pub struct Roster {
+    elves: Vec<Interval>,
+    by_start: Vec<usize>,
+}
+
+impl Roster {
+    fn new(pairs: Vec<Pair>) -> Self {
+        let elves = pairs
+            .into_iter()
+            .flat_map(|Pair { first, second }| [first, second])
+            .collect::<Vec<_>>();
+        let mut by_start = (0..elves.len()).collect::<Vec<_>>();
+        by_start.sort_by_key(|&i| elves[i]);
+        Roster { elves, by_start }
+    }
+
+    pub fn covering(&self, section: isize) -> Vec<usize> {
+        let n = self
+            .by_start
+            .partition_point(|&i| self.elves[i].start <= section);
+        let mut elves = self.by_start[..n]
+            .iter()
+            .filter(|&&i| self.elves[i].contains_point(section))
+            .map(|i| i + 1)
+            .collect::<Vec<_>>();
+        elves.sort();
+        elves
+    }
+
+    pub fn overlapping(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (k, &i) in self.by_start.iter().enumerate() {
+            for &j in &self.by_start[k + 1..] {
+                if self.elves[i].end < self.elves[j].start {
+                    break;
+                }
+                pairs.push((i.min(j) + 1, i.max(j) + 1));
+            }
+        }
+        pairs.sort();
+        pairs
+    }
+
+    // each pair is an elf followed by another elf whose sections it contains
+    pub fn containing(&self) -> Vec<(usize, usize)> {
+        let mut pairs = vec![];
+        for (a, b) in self.overlapping() {
+            if self.elves[a - 1].contains(self.elves[b - 1]) {
+                pairs.push((a, b));
+            }
+            if self.elves[b - 1].contains(self.elves[a - 1]) {
+                pairs.push((b, a));
+            }
+        }
+        pairs
+    }
+
+    // gaps between the lowest and highest assigned sections
+    pub fn uncovered(&self) -> Vec<Interval> {
+        interval::merge(self.elves.iter().copied())
+            .windows(2)
+            .map(|w| Interval::new(w[0].end + 1, w[1].start - 1))
+            .collect()
+    }
+}
+
+pub fn roster(input: &str) -> Roster {
+    Roster::new(parse(input))
 }
 
 #[cfg(test)]
@@ -82,4 +146,20 @@ mod tests {
     fn test_puzzle2_input() {
         assert_eq!(puzzle2(INPUT), 893);
     }
+
+    #[test]
+    fn test_reversed_assignments() {
+        assert_eq!(puzzle1("5-3,1-2\n4-4,5-3"), 1);
+        assert_eq!(puzzle2("5-3,1-2\n4-4,5-3"), 1);
+    }
+
+    #[test]
+    fn test_roster_example() {
+        let roster = roster(EXAMPLE);
+        assert_eq!(roster.covering(3), vec![1, 3, 7, 8, 11]);
+        assert_eq!(roster.covering(1), vec![]);
+        assert!(roster.containing().contains(&(7, 8)));
+        assert!(roster.overlapping().contains(&(1, 7)));
+        assert_eq!(roster.uncovered(), vec![]);
+    }
 }
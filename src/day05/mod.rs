@@ -1,72 +1,238 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt, iter,
+};
+
 use regex::Regex;
 
-struct Move {
-    quantity: usize,
-    from: usize,
-    to: usize,
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    NoMoves,
+    NoLabels,
+    BadLabel { line: usize, label: String },
+    DuplicateLabel { line: usize, label: usize },
+    BadMove { line: usize },
+    NoStack { line: usize, label: usize },
+    Empty { line: usize, label: usize },
+    Mismatch,
+    Unreachable { states: usize },
+    TooManyStates { limit: usize },
 }
 
-struct Drawing {
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoMoves => write!(f, "no blank line before the moves"),
+            Error::NoLabels => write!(f, "no stack labels"),
+            Error::BadLabel { line, label } => write!(f, "line {line}: bad label {label:?}"),
+            Error::DuplicateLabel { line, label } => {
+                write!(f, "line {line}: duplicate label {label}")
+            }
+            Error::BadMove { line } => write!(f, "line {line}: bad move"),
+            Error::NoStack { line, label } => write!(f, "line {line}: no stack {label}"),
+            Error::Empty { line, label } => {
+                write!(f, "line {line}: not enough crates on stack {label}")
+            }
+            Error::Mismatch => write!(f, "target has different stacks or crates"),
+            Error::Unreachable { states } => {
+                write!(f, "target unreachable from all {states} reachable states")
+            }
+            Error::TooManyStates { limit } => {
+                write!(f, "gave up after exploring {limit} states")
+            }
+        }
+    }
+}
+
+// `from` and `to` are indexes into the stacks rather than labels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+    // one-based, for errors, or 0 for moves that weren't parsed
+    pub line: usize,
+    pub quantity: usize,
+    pub from: usize,
+    pub to: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Stacks {
+    labels: Vec<usize>,
     crates: Vec<Vec<char>>,
-    moves: Vec<Move>,
 }
 
-fn parse(input: &str) -> Drawing {
-    let lines = input.lines().collect::<Vec<_>>();
-    let i = lines.iter().position(|&line| line.is_empty()).unwrap();
+struct Drawing {
+    stacks: Stacks,
+    moves: Vec<Move>,
+}
 
-    // assume there aren't more than 9 stacks
-    let mut n = 0;
-    while 1 + (n * 4) < lines[i - 1].len() {
-        n += 1;
+// each label along with the range of columns it spans
+fn parse_labels(n: usize, line: &str) -> Result<Vec<(usize, usize, usize)>, Error> {
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut labels: Vec<(usize, usize, usize)> = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let j = (i..chars.len())
+            .find(|&j| chars[j].is_whitespace())
+            .unwrap_or(chars.len());
+        let word = chars[i..j].iter().collect::<String>();
+        let label = word.parse().map_err(|_| Error::BadLabel {
+            line: n,
+            label: word,
+        })?;
+        if labels.iter().any(|&(other, _, _)| other == label) {
+            return Err(Error::DuplicateLabel { line: n, label });
+        }
+        labels.push((label, i, j - 1));
+        i = j;
+    }
+    if labels.is_empty() {
+        return Err(Error::NoLabels);
     }
+    Ok(labels)
+}
+
+// the label under the given column, or else the one closest to it
+fn nearest(labels: &[(usize, usize, usize)], column: usize) -> usize {
+    (0..labels.len())
+        .min_by_key(|&k| {
+            let (_, start, end) = labels[k];
+            if column < start {
+                start - column
+            } else {
+                column.saturating_sub(end)
+            }
+        })
+        .unwrap()
+}
 
-    let mut crates = vec![vec![]; n];
+// the drawing is every line before `i`, ending with the labels
+fn parse_stacks(lines: &[&str], i: usize) -> Result<Stacks, Error> {
+    if i == 0 {
+        return Err(Error::NoLabels);
+    }
+    let labels = parse_labels(i, lines[i - 1])?;
+    let mut crates = vec![vec![]; labels.len()];
     for j in (0..(i - 1)).rev() {
         let line = lines[j].chars().collect::<Vec<_>>();
-        let mut k = 0;
-        while k < n {
-            if let Some(&c) = line.get(1 + (k * 4)) {
-                if c != ' ' {
-                    crates[k].push(c);
-                }
+        for k in 0..line.len().saturating_sub(2) {
+            if line[k] == '[' && line[k + 2] == ']' {
+                crates[nearest(&labels, k + 1)].push(line[k + 1]);
             }
-            k += 1;
         }
     }
+    let labels = labels.into_iter().map(|(label, _, _)| label).collect();
+    Ok(Stacks { labels, crates })
+}
+
+// just a drawing, without moves
+pub fn stacks(input: &str) -> Result<Stacks, Error> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let i = lines
+        .iter()
+        .position(|&line| line.trim().is_empty())
+        .unwrap_or(lines.len());
+    parse_stacks(&lines, i)
+}
+
+fn parse(input: &str) -> Result<Drawing, Error> {
+    let lines = input.lines().collect::<Vec<_>>();
+    let i = lines
+        .iter()
+        .position(|&line| line.trim().is_empty())
+        .ok_or(Error::NoMoves)?;
+    let stacks = parse_stacks(&lines, i)?;
+    let labels = &stacks.labels;
 
     let mut moves = vec![];
     let re = Regex::new(r"^move (\d+) from (\d+) to (\d+)$").unwrap();
-    for &line in &lines[(i + 1)..] {
-        let cap = re.captures(line).unwrap();
+    for (j, &line) in lines.iter().enumerate().skip(i + 1) {
+        let line_number = j + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let cap = re
+            .captures(line.trim())
+            .ok_or(Error::BadMove { line: line_number })?;
+        let stack = |label: &str| {
+            let label = label
+                .parse()
+                .map_err(|_| Error::BadMove { line: line_number })?;
+            labels
+                .iter()
+                .position(|&l| l == label)
+                .ok_or(Error::NoStack {
+                    line: line_number,
+                    label,
+                })
+        };
         moves.push(Move {
-            quantity: cap[1].parse().unwrap(),
-            from: cap[2].parse::<usize>().unwrap() - 1,
-            to: cap[3].parse::<usize>().unwrap() - 1,
+            line: line_number,
+            quantity: cap[1]
+                .parse()
+                .map_err(|_| Error::BadMove { line: line_number })?,
+            from: stack(&cap[2])?,
+            to: stack(&cap[3])?,
         });
     }
 
-    Drawing { crates, moves }
+    Ok(Drawing { stacks, moves })
 }
 
-fn tops(crates: Vec<Vec<char>>) -> String {
-    crates
-        .into_iter()
-        .map(|c| c.last().unwrap().to_string())
-        .collect()
+impl Stacks {
+    // one character per stack, with a space for an empty one so the answer lines up with labels
+    fn tops(&self) -> String {
+        self.crates
+            .iter()
+            .map(|c| c.last().copied().unwrap_or(' '))
+            .collect()
+    }
+
+    fn apply(&mut self, crane: &dyn Crane, m: &Move) -> Result<(), Error> {
+        if self.crates[m.from].len() < m.quantity {
+            return Err(Error::Empty {
+                line: m.line,
+                label: self.labels[m.from],
+            });
+        }
+        if m.from != m.to {
+            let (from, to) = two_refs(&mut self.crates, m.from, m.to);
+            crane.lift(from, to, m.quantity);
+        }
+        Ok(())
+    }
 }
 
-pub fn puzzle1(input: &str) -> String {
-    let Drawing { mut crates, moves } = parse(input);
-    for Move { quantity, from, to } in moves {
-        let mut i = 0;
-        while i < quantity {
-            let c = crates[from].pop().unwrap();
-            crates[to].push(c);
-            i += 1;
+// same layout as the puzzle input, with each label centered under its stack
+impl fmt::Display for Stacks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let height = self.crates.iter().map(|c| c.len()).max().unwrap_or(0);
+        for row in (0..height).rev() {
+            let line = self
+                .crates
+                .iter()
+                .map(|c| match c.get(row) {
+                    Some(c) => format!("[{c}]"),
+                    None => "   ".to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(f, "{}", line.trim_end())?;
         }
+        let mut line = vec![];
+        for (k, label) in self.labels.iter().enumerate() {
+            let label = label.to_string();
+            let start = (1 + k * 4)
+                .saturating_sub((label.len() - 1) / 2)
+                .max(line.len());
+            line.resize(start, ' ');
+            line.extend(label.chars());
+        }
+        writeln!(f, "{}", line.into_iter().collect::<String>())
     }
-    tops(crates)
 }
 
 fn two_refs<T>(v: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
@@ -81,15 +247,175 @@ fn two_refs<T>(v: &mut [T], i: usize, j: usize) -> (&mut T, &mut T) {
     (&mut l[i], &mut r[0])
 }
 
-pub fn puzzle2(input: &str) -> String {
-    let Drawing { mut crates, moves } = parse(input);
-    for Move { quantity, from, to } in moves {
-        let (f, t) = two_refs(&mut crates, from, to);
-        let i = f.len() - quantity;
-        t.extend_from_slice(f[i..].as_ref());
-        f.truncate(i);
+pub trait Crane {
+    // `from` always has at least `quantity` crates
+    fn lift(&self, from: &mut Vec<char>, to: &mut Vec<char>, quantity: usize);
+}
+
+// one crate at a time
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn lift(&self, from: &mut Vec<char>, to: &mut Vec<char>, quantity: usize) {
+        let mut i = 0;
+        while i < quantity {
+            let c = from.pop().unwrap();
+            to.push(c);
+            i += 1;
+        }
+    }
+}
+
+// all crates at once
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn lift(&self, from: &mut Vec<char>, to: &mut Vec<char>, quantity: usize) {
+        let i = from.len() - quantity;
+        to.extend_from_slice(from[i..].as_ref());
+        from.truncate(i);
+    }
+}
+
+// at most this many crates at a time
+pub struct Chunked(pub usize);
+
+impl Crane for Chunked {
+    fn lift(&self, from: &mut Vec<char>, to: &mut Vec<char>, quantity: usize) {
+        let mut left = quantity;
+        while left > 0 {
+            let n = left.min(self.0);
+            CrateMover9001.lift(from, to, n);
+            left -= n;
+        }
+    }
+}
+
+pub fn crane(name: &str) -> Option<Box<dyn Crane>> {
+    match name {
+        "9000" => Some(Box::new(CrateMover9000)),
+        "9001" => Some(Box::new(CrateMover9001)),
+        _ => match name.parse() {
+            Ok(0) | Err(_) => None,
+            Ok(k) => Some(Box::new(Chunked(k))),
+        },
+    }
+}
+
+// the starting stacks followed by the stacks after each move, stopping at the first error
+pub fn replay<'a>(
+    input: &str,
+    crane: &'a dyn Crane,
+) -> Result<impl Iterator<Item = Result<Stacks, Error>> + 'a, Error> {
+    let Drawing { mut stacks, moves } = parse(input)?;
+    let start = stacks.clone();
+    let mut moves = moves.into_iter();
+    let mut failed = false;
+    let rest = iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        let m = moves.next()?;
+        match stacks.apply(crane, &m) {
+            Ok(()) => Some(Ok(stacks.clone())),
+            Err(err) => {
+                failed = true;
+                Some(Err(err))
+            }
+        }
+    });
+    Ok(iter::once(Ok(start)).chain(rest))
+}
+
+// breadth-first over every reachable arrangement, so if the target isn't found then it can't be;
+// only the move into each state and the index of the state it came from are kept, and the search
+// gives up after `limit` states
+pub fn plan(
+    start: &Stacks,
+    target: &Stacks,
+    crane: &dyn Crane,
+    limit: usize,
+) -> Result<Vec<Move>, Error> {
+    let sorted = |stacks: &Stacks| {
+        let mut crates = stacks.crates.concat();
+        crates.sort();
+        crates
+    };
+    if start.labels != target.labels || sorted(start) != sorted(target) {
+        return Err(Error::Mismatch);
+    }
+
+    let mut seen = HashMap::from([(start.clone(), 0)]);
+    let mut parents: Vec<Option<(usize, Move)>> = vec![None];
+    let mut queue = VecDeque::from([(start.clone(), 0)]);
+    while let Some((stacks, i)) = queue.pop_front() {
+        if &stacks == target {
+            let mut moves = vec![];
+            let mut current = i;
+            while let Some((parent, m)) = parents[current] {
+                moves.push(m);
+                current = parent;
+            }
+            moves.reverse();
+            return Ok(moves);
+        }
+        for from in 0..stacks.crates.len() {
+            for to in 0..stacks.crates.len() {
+                if from == to {
+                    continue;
+                }
+                for quantity in 1..=stacks.crates[from].len() {
+                    let m = Move {
+                        line: 0,
+                        quantity,
+                        from,
+                        to,
+                    };
+                    let mut next = stacks.clone();
+                    next.apply(crane, &m).unwrap();
+                    if !seen.contains_key(&next) {
+                        if parents.len() == limit {
+                            return Err(Error::TooManyStates { limit });
+                        }
+                        seen.insert(next.clone(), parents.len());
+                        queue.push_back((next, parents.len()));
+                        parents.push(Some((i, m)));
+                    }
+                }
+            }
+        }
+    }
+    Err(Error::Unreachable {
+        states: parents.len(),
+    })
+}
+
+// the start and the moves as puzzle input
+pub fn instructions(start: &Stacks, moves: &[Move]) -> String {
+    let mut out = format!("{}\n", start);
+    for m in moves {
+        out += &format!(
+            "move {} from {} to {}\n",
+            m.quantity, start.labels[m.from], start.labels[m.to]
+        );
+    }
+    out
+}
+
+fn solve(input: &str, crane: &dyn Crane) -> Result<String, Error> {
+    let Drawing { mut stacks, moves } = parse(input)?;
+    for m in moves {
+        stacks.apply(crane, &m)?;
     }
-    tops(crates)
+    Ok(stacks.tops())
+}
+
+pub fn puzzle1(input: &str) -> Result<String, Error> {
+    solve(input, &CrateMover9000)
+}
+
+pub fn puzzle2(input: &str) -> Result<String, Error> {
+    solve(input, &CrateMover9001)
 }
 
 #[cfg(test)]
@@ -101,21 +427,121 @@ mod tests {
 
     #[test]
     fn test_puzzle1_example() {
-        assert_eq!(puzzle1(EXAMPLE), "CMZ");
+        assert_eq!(puzzle1(EXAMPLE), Ok("CMZ".to_string()));
     }
 
     #[test]
     fn test_puzzle1_input() {
-        assert_eq!(puzzle1(INPUT), "FZCMJCRHZ");
+        assert_eq!(puzzle1(INPUT), Ok("FZCMJCRHZ".to_string()));
     }
 
     #[test]
     fn test_puzzle2_example() {
-        assert_eq!(puzzle2(EXAMPLE), "MCD");
+        assert_eq!(puzzle2(EXAMPLE), Ok("MCD".to_string()));
     }
 
     #[test]
     fn test_puzzle2_input() {
-        assert_eq!(puzzle2(INPUT), "JSDHQMZGF");
+        assert_eq!(puzzle2(INPUT), Ok("JSDHQMZGF".to_string()));
+    }
+
+    #[test]
+    fn test_many_stacks() {
+        let input = concat!(
+            "                                    [K]\n",
+            "[A]                                 [L] [M]\n",
+            "[B] [C] [D] [E] [F] [G] [H] [I] [J] [N] [O]\n",
+            " 1   2   3   4   5   6   7   8   9   10  11\n",
+            "\n",
+            "move 2 from 10 to 1\n",
+            "move 1 from 11 to 3\n",
+        );
+        assert_eq!(puzzle1(input), Ok("LCMEFGHIJNO".to_string()));
+        assert_eq!(puzzle2(input), Ok("KCMEFGHIJNO".to_string()));
+    }
+
+    #[test]
+    fn test_empty_stack() {
+        let drawing = EXAMPLE.split("\n\n").next().unwrap();
+        let input = format!("{drawing}\n\nmove 1 from 3 to 1\n");
+        assert_eq!(puzzle1(&input), Ok("PD ".to_string()));
+    }
+
+    #[test]
+    fn test_chunked() {
+        assert_eq!(solve(EXAMPLE, &Chunked(1)), puzzle1(EXAMPLE));
+        assert_eq!(solve(INPUT, &Chunked(usize::MAX)), puzzle2(INPUT));
+        assert_eq!(solve(INPUT, &Chunked(2)), Ok("RHCSPBNGW".to_string()));
+    }
+
+    #[test]
+    fn test_replay_example() {
+        let states = replay(EXAMPLE, &CrateMover9000)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(states.len(), 5);
+        assert_eq!(
+            states[0].to_string(),
+            EXAMPLE[..EXAMPLE.find("\n\n").unwrap() + 1]
+        );
+        assert_eq!(
+            states[2].to_string(),
+            "        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3\n",
+        );
+    }
+
+    #[test]
+    fn test_plan_example() {
+        let start = stacks(EXAMPLE).unwrap();
+        let target = replay(EXAMPLE, &CrateMover9001)
+            .unwrap()
+            .last()
+            .unwrap()
+            .unwrap();
+        let moves = plan(&start, &target, &CrateMover9001, 100_000).unwrap();
+        assert_eq!(moves.len(), 4);
+        let input = instructions(&start, &moves);
+        assert_eq!(puzzle2(&input), puzzle2(EXAMPLE));
+    }
+
+    #[test]
+    fn test_plan_unreachable() {
+        let start = stacks("[A] [B]\n 1   2").unwrap();
+        let target = stacks("[A]\n[B]\n 1   2").unwrap();
+        assert!(plan(&start, &target, &CrateMover9001, 10).is_ok());
+        let start = stacks("[A]\n[B]\n 1").unwrap();
+        let target = stacks("[B]\n[A]\n 1").unwrap();
+        assert_eq!(
+            plan(&start, &target, &CrateMover9000, 10),
+            Err(Error::Unreachable { states: 1 }),
+        );
+        let target = stacks("[A] [C]\n 1   2").unwrap();
+        assert_eq!(
+            plan(&start, &target, &CrateMover9001, 10),
+            Err(Error::Mismatch)
+        );
+    }
+
+    #[test]
+    fn test_plan_limit() {
+        let start = stacks(INPUT).unwrap();
+        let target = replay(INPUT, &CrateMover9001)
+            .unwrap()
+            .last()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            plan(&start, &target, &CrateMover9001, 10000),
+            Err(Error::TooManyStates { limit: 10000 }),
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = "[A]\n 1 \n\nmove 1 from 1 to 2";
+        assert_eq!(puzzle1(input), Err(Error::NoStack { line: 4, label: 2 }));
+        let input = "[A] [B]\n 1   2\n\nmove 2 from 1 to 2";
+        assert_eq!(puzzle2(input), Err(Error::Empty { line: 4, label: 1 }));
     }
 }
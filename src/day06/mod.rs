@@ -1,13 +1,175 @@
-use std::collections::HashSet;
+use std::{
+    collections::VecDeque,
+    fmt,
+    io::{self, BufReader, Bytes, Read},
+    mem,
+};
+
+// tracks the last `n` bytes and how many of them repeat an earlier byte in the window
+pub struct Detector {
+    n: usize,
+    window: VecDeque<u8>,
+    counts: [usize; 256],
+    dups: usize,
+}
+
+impl Detector {
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "window size must be positive");
+        Detector {
+            n,
+            window: VecDeque::with_capacity(n),
+            counts: [0; 256],
+            dups: 0,
+        }
+    }
+
+    // whether the last `n` bytes, ending with this one, are all different
+    pub fn push(&mut self, b: u8) -> bool {
+        if self.window.len() == self.n {
+            let old = self.window.pop_front().unwrap();
+            self.counts[old as usize] -= 1;
+            if self.counts[old as usize] > 0 {
+                self.dups -= 1;
+            }
+        }
+        if self.counts[b as usize] > 0 {
+            self.dups += 1;
+        }
+        self.counts[b as usize] += 1;
+        self.window.push_back(b);
+        self.window.len() == self.n && self.dups == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.window.clear();
+        self.counts = [0; 256];
+        self.dups = 0;
+    }
+}
+
+// number of bytes read through the end of each marker
+pub fn markers(reader: impl Read, n: usize) -> impl Iterator<Item = io::Result<usize>> {
+    let mut detector = Detector::new(n);
+    BufReader::new(reader)
+        .bytes()
+        .enumerate()
+        .filter_map(move |(i, b)| match b {
+            Ok(b) => detector.push(b).then_some(Ok(i + 1)),
+            Err(err) => Some(Err(err)),
+        })
+}
 
 fn solve(s: &str, n: usize) -> usize {
-    let v = s.chars().collect::<Vec<_>>();
-    for i in 0..=(v.len() - n) {
-        if v[i..i + n].iter().copied().collect::<HashSet<_>>().len() == n {
-            return i + n;
+    markers(s.as_bytes(), n).next().unwrap().unwrap()
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Frame {
+    Packet(Vec<u8>),
+    Message(Vec<u8>),
+    Corrupt(Vec<u8>),
+}
+
+impl fmt::Display for Frame {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, bytes) = match self {
+            Frame::Packet(bytes) => ("packet", bytes),
+            Frame::Message(bytes) => ("message", bytes),
+            Frame::Corrupt(bytes) => ("corrupt", bytes),
+        };
+        write!(f, "{kind}: {}", String::from_utf8_lossy(bytes))
+    }
+}
+
+enum State {
+    Seeking,
+    Packet,
+    Message,
+}
+
+// After any noise, a signal alternates between packets and messages: each packet starts after a
+// start-of-packet marker and ends with a start-of-message marker, after which the message runs
+// until the next start-of-packet marker. Markers are not part of the frames. A frame longer than
+// `max_len` is reported as corrupt, and then decoding resumes at the next start-of-packet marker.
+pub struct Decoder<R> {
+    bytes: Bytes<BufReader<R>>,
+    packet: Detector,
+    message: Detector,
+    max_len: usize,
+    state: State,
+    buf: Vec<u8>,
+}
+
+pub fn decode<R: Read>(reader: R, packet: usize, message: usize, max_len: usize) -> Decoder<R> {
+    Decoder {
+        bytes: BufReader::new(reader).bytes(),
+        packet: Detector::new(packet),
+        message: Detector::new(message),
+        max_len,
+        state: State::Seeking,
+        buf: vec![],
+    }
+}
+
+impl<R: Read> Decoder<R> {
+    // the frame so far, without the marker that just ended it
+    fn take(&mut self, marker: usize) -> Vec<u8> {
+        self.buf.truncate(self.buf.len() - marker);
+        mem::take(&mut self.buf)
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let b = match self.bytes.next() {
+                Some(Ok(b)) => b,
+                Some(Err(err)) => return Some(Err(err)),
+                None => {
+                    let buf = mem::take(&mut self.buf);
+                    return match mem::replace(&mut self.state, State::Seeking) {
+                        State::Seeking => None,
+                        State::Packet if buf.is_empty() => None,
+                        State::Packet => Some(Ok(Frame::Corrupt(buf))),
+                        State::Message => Some(Ok(Frame::Message(buf))),
+                    };
+                }
+            };
+            match self.state {
+                State::Seeking => {
+                    if self.packet.push(b) {
+                        self.packet.clear();
+                        self.state = State::Packet;
+                    }
+                }
+                State::Packet => {
+                    self.buf.push(b);
+                    if self.message.push(b) {
+                        self.message.clear();
+                        self.state = State::Message;
+                        return Some(Ok(Frame::Packet(self.take(self.message.n))));
+                    }
+                }
+                State::Message => {
+                    self.buf.push(b);
+                    if self.packet.push(b) {
+                        self.packet.clear();
+                        self.state = State::Packet;
+                        return Some(Ok(Frame::Message(self.take(self.packet.n))));
+                    }
+                }
+            }
+            if self.buf.len() > self.max_len + self.packet.n.max(self.message.n) {
+                self.packet.clear();
+                self.message.clear();
+                self.state = State::Seeking;
+                return Some(Ok(Frame::Corrupt(mem::take(&mut self.buf))));
+            }
         }
     }
-    panic!()
 }
 
 pub fn puzzle1(input: &str) -> usize {
@@ -20,6 +182,8 @@ pub fn puzzle2(input: &str) -> usize {
 
 #[cfg(test)]
 mod tests {
+    use itertools::Itertools;
+
     use super::*;
 
     const EXAMPLE0: &str = "mjqjpqmgbljsphdztnvjfqwrcgsmlb";
@@ -88,4 +252,45 @@ mod tests {
     fn test_puzzle2_input() {
         assert_eq!(puzzle2(INPUT), 3534);
     }
+
+    #[test]
+    fn test_markers_input() {
+        let bytes = INPUT.trim_end().as_bytes();
+        for n in 1..=14 {
+            let all = markers(bytes, n).collect::<io::Result<Vec<_>>>().unwrap();
+            let expected = (n..=bytes.len())
+                .filter(|&i| bytes[i - n..i].iter().all_unique())
+                .collect::<Vec<_>>();
+            assert_eq!(all, expected);
+        }
+    }
+
+    #[test]
+    fn test_decode() {
+        let signal = "aaaaabcd".to_string()
+            + "xxyyAAABCDEF"
+            + "hhiiijkl"
+            + &"z".repeat(15)
+            + "qqqqrst"
+            + "uu";
+        let frames = decode(signal.as_bytes(), 4, 6, 8)
+            .collect::<io::Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(
+            frames,
+            vec![
+                Frame::Packet(b"xxyyAA".to_vec()),
+                Frame::Message(b"hhii".to_vec()),
+                Frame::Corrupt("z".repeat(15).into_bytes()),
+                Frame::Corrupt(b"uu".to_vec()),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_markers_long() {
+        let input = "ab".repeat(1_000_000) + "abc";
+        let all = markers(input.as_bytes(), 3).collect::<io::Result<Vec<_>>>();
+        assert_eq!(all.unwrap(), vec![2_000_003]);
+    }
 }
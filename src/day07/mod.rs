@@ -1,136 +1,495 @@
-use std::collections::HashMap;
+use std::{collections::BTreeMap, fmt, fs as stdfs, io, path::Path};
 
-enum Entry<'a> {
-    File(usize, &'a str),
-    Dir(&'a str),
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadLine {
+        line: usize,
+    },
+    NoLs {
+        line: usize,
+    },
+    AboveRoot {
+        line: usize,
+    },
+    NotDir {
+        line: usize,
+        path: String,
+    },
+    NotFile {
+        line: usize,
+        path: String,
+    },
+    SizeMismatch {
+        line: usize,
+        path: String,
+        old: usize,
+        new: usize,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadLine { line } => write!(f, "line {line}: can't parse"),
+            Error::NoLs { line } => write!(f, "line {line}: listing without `ls`"),
+            Error::AboveRoot { line } => write!(f, "line {line}: `cd ..` from the root"),
+            Error::NotDir { line, path } => write!(f, "line {line}: {path} is a file"),
+            Error::NotFile { line, path } => write!(f, "line {line}: {path} is a directory"),
+            Error::SizeMismatch {
+                line,
+                path,
+                old,
+                new,
+            } => write!(f, "line {line}: {path} was {old} but is now {new}"),
+        }
+    }
 }
 
-enum Cmd<'a> {
+enum Line<'a> {
     Cd(&'a str),
-    CdOut,
-    Ls(Vec<Entry<'a>>),
-}
-
-fn parse(input: &str) -> Vec<Cmd> {
-    use Cmd::*;
-    use Entry::*;
-    let mut cmds = vec![];
-    let mut entries = vec![];
-    for line in input.lines().skip(1) {
-        let mut words = line.split_whitespace();
-        match words.next().unwrap() {
-            "$" => {
-                if !entries.is_empty() {
-                    cmds.push(Ls(entries));
-                    entries = vec![];
-                }
-                if let "cd" = words.next().unwrap() {
-                    cmds.push(match words.next().unwrap() {
-                        ".." => CdOut,
-                        dir => Cd(dir),
-                    });
+    Ls,
+    // any other command, whose output is ignored
+    Other,
+    Dir(&'a str),
+    File(usize, &'a str),
+}
+
+fn parse_line(line: &str) -> Option<Line<'_>> {
+    let mut words = line.split_whitespace();
+    let line = match (words.next()?, words.next()) {
+        ("$", Some("cd")) => Line::Cd(words.next()?),
+        ("$", Some("ls")) => Line::Ls,
+        ("$", _) => return Some(Line::Other),
+        ("dir", Some(name)) => Line::Dir(name),
+        (size, Some(name)) => Line::File(size.parse().ok()?, name),
+        _ => return None,
+    };
+    match words.next() {
+        Some(_) => None,
+        None => Some(line),
+    }
+}
+
+pub enum Kind {
+    Dir(BTreeMap<String, usize>),
+    File,
+}
+
+pub struct Node {
+    pub name: String,
+    pub parent: Option<usize>,
+    pub kind: Kind,
+    // for a directory, the total of everything under it
+    pub size: usize,
+}
+
+// nodes are referred to by their index, and the root is always at index 0
+pub struct Fs {
+    nodes: Vec<Node>,
+}
+
+impl Fs {
+    pub fn new() -> Self {
+        Fs {
+            nodes: vec![Node {
+                name: String::new(),
+                parent: None,
+                kind: Kind::Dir(BTreeMap::new()),
+                size: 0,
+            }],
+        }
+    }
+
+    pub fn node(&self, id: usize) -> &Node {
+        &self.nodes[id]
+    }
+
+    fn child(&self, dir: usize, name: &str) -> Option<usize> {
+        match &self.nodes[dir].kind {
+            Kind::Dir(children) => children.get(name).copied(),
+            Kind::File => None,
+        }
+    }
+
+    fn insert(&mut self, dir: usize, name: &str, kind: Kind) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(dir),
+            kind,
+            size: 0,
+        });
+        match &mut self.nodes[dir].kind {
+            Kind::Dir(children) => children.insert(name.to_string(), id),
+            Kind::File => panic!("{} is not a directory", self.path(dir)),
+        };
+        id
+    }
+
+    pub fn mkdir(&mut self, dir: usize, name: &str) -> usize {
+        match self.child(dir, name) {
+            Some(id) => id,
+            None => self.insert(dir, name, Kind::Dir(BTreeMap::new())),
+        }
+    }
+
+    pub fn touch(&mut self, dir: usize, name: &str, size: usize) -> usize {
+        let id = match self.child(dir, name) {
+            Some(id) => id,
+            None => self.insert(dir, name, Kind::File),
+        };
+        let old = self.nodes[id].size;
+        let mut node = Some(id);
+        while let Some(i) = node {
+            self.nodes[i].size = self.nodes[i].size - old + size;
+            node = self.nodes[i].parent;
+        }
+        id
+    }
+
+    pub fn lookup(&self, path: &str) -> Option<usize> {
+        let mut id = 0;
+        for name in path.strip_prefix('/')?.split('/') {
+            if !name.is_empty() {
+                id = self.child(id, name)?;
+            }
+        }
+        Some(id)
+    }
+
+    pub fn path(&self, id: usize) -> String {
+        let mut names = vec![];
+        let mut node = id;
+        while let Some(parent) = self.nodes[node].parent {
+            names.push(self.nodes[node].name.as_str());
+            node = parent;
+        }
+        if names.is_empty() {
+            return "/".to_string();
+        }
+        names.reverse();
+        names.iter().map(|name| format!("/{name}")).collect()
+    }
+
+    // everything under `root` (inclusive) in depth-first order
+    pub fn find(&self, root: usize, query: &Query) -> Vec<usize> {
+        let mut found = vec![];
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id];
+            if query.matches(node) {
+                found.push(id);
+            }
+            if let Kind::Dir(children) = &node.kind {
+                stack.extend(children.values().rev());
+            }
+        }
+        found
+    }
+
+    // in the same format as the example in the puzzle
+    pub fn tree(&self, root: usize) -> String {
+        let mut out = String::new();
+        let mut stack = vec![(root, 0)];
+        while let Some((id, depth)) = stack.pop() {
+            let node = &self.nodes[id];
+            let name = if id == 0 { "/" } else { &node.name };
+            let kind = match &node.kind {
+                Kind::Dir(children) => {
+                    stack.extend(children.values().rev().map(|&child| (child, depth + 1)));
+                    "dir"
                 }
+                Kind::File => "file",
+            };
+            out += &format!(
+                "{:indent$}- {name} ({kind}, size={})\n",
+                "",
+                node.size,
+                indent = depth * 2
+            );
+        }
+        out
+    }
+
+    // every directory, largest first
+    pub fn du(&self, root: usize) -> String {
+        let query = Query {
+            dirs: true,
+            ..Default::default()
+        };
+        let mut dirs = self.find(root, &query);
+        dirs.sort_by_key(|&id| std::cmp::Reverse(self.nodes[id].size));
+        dirs.into_iter()
+            .map(|id| format!("{}\t{}\n", human(self.nodes[id].size), self.path(id)))
+            .collect()
+    }
+
+    pub fn json(&self, root: usize) -> String {
+        let node = &self.nodes[root];
+        let name = if root == 0 { "/" } else { &node.name };
+        let mut name_json = String::new();
+        for c in name.chars() {
+            match c {
+                '"' => name_json += "\\\"",
+                '\\' => name_json += "\\\\",
+                c if c.is_control() => name_json += &format!("\\u{:04x}", c as u32),
+                c => name_json.push(c),
             }
-            left => {
-                let name = words.next().unwrap();
-                entries.push(match left {
-                    "dir" => Dir(name),
-                    size => File(size.parse().unwrap(), name),
-                });
+        }
+        match &node.kind {
+            Kind::Dir(children) => {
+                let children = children
+                    .values()
+                    .map(|&child| self.json(child))
+                    .collect::<Vec<_>>();
+                format!(
+                    r#"{{"name":"{name_json}","type":"dir","size":{},"children":[{}]}}"#,
+                    node.size,
+                    children.join(","),
+                )
             }
+            Kind::File => format!(
+                r#"{{"name":"{name_json}","type":"file","size":{}}}"#,
+                node.size
+            ),
         }
     }
-    if !entries.is_empty() {
-        cmds.push(Ls(entries));
+}
+
+impl Fs {
+    // symlinks are skipped, as are names that can't appear in a transcript
+    fn scan_into(&mut self, dir: usize, path: &Path) -> io::Result<()> {
+        for entry in stdfs::read_dir(path)? {
+            let entry = entry?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) if !name.is_empty() && !name.contains(char::is_whitespace) => name,
+                _ => continue,
+            };
+            let meta = entry.path().symlink_metadata()?;
+            if meta.is_dir() {
+                let child = self.mkdir(dir, &name);
+                self.scan_into(child, &entry.path())?;
+            } else if meta.is_file() {
+                self.touch(dir, &name, meta.len() as usize);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn scan(path: &Path) -> io::Result<Self> {
+        let mut fs = Fs::new();
+        fs.scan_into(0, path)?;
+        Ok(fs)
+    }
+
+    // commands and output that would produce this filesystem, in the puzzle input format
+    pub fn transcript(&self) -> String {
+        let mut out = "$ cd /\n".to_string();
+        self.write_transcript(0, &mut out);
+        out
+    }
+
+    fn write_transcript(&self, dir: usize, out: &mut String) {
+        let Kind::Dir(children) = &self.nodes[dir].kind else {
+            return;
+        };
+        *out += "$ ls\n";
+        for (name, &id) in children {
+            match self.nodes[id].kind {
+                Kind::Dir(_) => *out += &format!("dir {name}\n"),
+                Kind::File => *out += &format!("{} {name}\n", self.nodes[id].size),
+            }
+        }
+        for (name, &id) in children {
+            if let Kind::Dir(_) = self.nodes[id].kind {
+                *out += &format!("$ cd {name}\n");
+                self.write_transcript(id, out);
+                *out += "$ cd ..\n";
+            }
+        }
     }
-    cmds
 }
 
-enum Fs<'a> {
-    Dir(HashMap<&'a str, Fs<'a>>),
-    File(usize),
+// like `du -h`, in powers of 1024
+fn human(size: usize) -> String {
+    let mut value = size as f64;
+    for unit in ["", "K", "M", "G", "T"] {
+        if value < 1024.0 || unit == "T" {
+            return if unit.is_empty() {
+                size.to_string()
+            } else if value < 10.0 {
+                format!("{:.1}{unit}", value)
+            } else {
+                format!("{:.0}{unit}", value)
+            };
+        }
+        value /= 1024.0;
+    }
+    unreachable!()
 }
 
-fn explore<'a>(dir: &mut HashMap<&'a str, Fs<'a>>, cmds: &mut impl Iterator<Item = Cmd<'a>>) {
-    while let Some(cmd) = cmds.next() {
-        match cmd {
-            Cmd::CdOut => return,
-            Cmd::Cd(name) => {
-                if !dir.contains_key(name) {
-                    dir.insert(name, Fs::Dir(HashMap::new()));
-                }
-                if let Some(Fs::Dir(child)) = dir.get_mut(name) {
-                    explore(child, cmds);
+#[derive(Default)]
+pub struct Query {
+    pub dirs: bool,
+    pub files: bool,
+    pub min: Option<usize>,
+    pub max: Option<usize>,
+    pub name: Option<String>,
+}
+
+impl Query {
+    fn matches(&self, node: &Node) -> bool {
+        let kind = match node.kind {
+            Kind::Dir(_) => self.dirs,
+            Kind::File => self.files,
+        };
+        kind && self.min.is_none_or(|min| min <= node.size)
+            && self.max.is_none_or(|max| node.size <= max)
+            && self.name.as_ref().is_none_or(|glob| {
+                matches_glob(
+                    &glob.chars().collect::<Vec<_>>(),
+                    &node.name.chars().collect::<Vec<_>>(),
+                )
+            })
+    }
+}
+
+// `*` matches any run of characters and `?` matches any one character
+fn matches_glob(glob: &[char], name: &[char]) -> bool {
+    match glob.split_first() {
+        None => name.is_empty(),
+        Some(('*', rest)) => (0..=name.len()).any(|i| matches_glob(rest, &name[i..])),
+        Some((&c, rest)) => match name.split_first() {
+            Some((&d, name)) => (c == '?' || c == d) && matches_glob(rest, name),
+            None => false,
+        },
+    }
+}
+
+enum Mode {
+    Start,
+    Listing,
+    Skipping,
+}
+
+pub fn filesystem(input: &str) -> Result<Fs, Error> {
+    let mut fs = Fs::new();
+    // every directory from the root to the current one
+    let mut cwd = vec![0];
+    let mut mode = Mode::Start;
+    for (i, line) in input.lines().enumerate() {
+        let n = i + 1;
+        if line.trim().is_empty() || matches!(mode, Mode::Skipping) && !line.starts_with('$') {
+            continue;
+        }
+        let dir = *cwd.last().unwrap();
+        match parse_line(line).ok_or(Error::BadLine { line: n })? {
+            Line::Cd(path) => {
+                mode = Mode::Start;
+                if path.starts_with('/') {
+                    cwd.truncate(1);
                 }
-            }
-            Cmd::Ls(entries) => {
-                for entry in entries {
-                    match entry {
-                        Entry::Dir(name) => {
-                            if !dir.contains_key(name) {
-                                dir.insert(name, Fs::Dir(HashMap::new()));
+                for name in path.split('/').filter(|name| !name.is_empty()) {
+                    match name {
+                        "." => {}
+                        ".." => {
+                            if cwd.len() == 1 {
+                                return Err(Error::AboveRoot { line: n });
                             }
+                            cwd.pop();
                         }
-                        Entry::File(size, name) => {
-                            dir.insert(name, Fs::File(size));
-                        }
+                        _ => cwd.push(mkdir(&mut fs, *cwd.last().unwrap(), name, n)?),
                     }
                 }
             }
+            Line::Ls => mode = Mode::Listing,
+            Line::Other => mode = Mode::Skipping,
+            Line::Dir(name) => match mode {
+                Mode::Listing => {
+                    mkdir(&mut fs, dir, name, n)?;
+                }
+                _ => return Err(Error::NoLs { line: n }),
+            },
+            Line::File(size, name) => match mode {
+                Mode::Listing => match fs.child(dir, name) {
+                    None => {
+                        fs.touch(dir, name, size);
+                    }
+                    Some(id) => {
+                        let node = fs.node(id);
+                        if let Kind::Dir(_) = node.kind {
+                            return Err(Error::NotFile {
+                                line: n,
+                                path: fs.path(id),
+                            });
+                        }
+                        if node.size != size {
+                            return Err(Error::SizeMismatch {
+                                line: n,
+                                path: fs.path(id),
+                                old: node.size,
+                                new: size,
+                            });
+                        }
+                    }
+                },
+                _ => return Err(Error::NoLs { line: n }),
+            },
         }
     }
+    Ok(fs)
 }
 
-fn get_size(fs: &Fs) -> usize {
-    match fs {
-        Fs::Dir(dir) => dir.values().map(get_size).sum(),
-        &Fs::File(size) => size,
+fn mkdir(fs: &mut Fs, dir: usize, name: &str, line: usize) -> Result<usize, Error> {
+    match fs.child(dir, name) {
+        Some(id) => match fs.node(id).kind {
+            Kind::Dir(_) => Ok(id),
+            Kind::File => Err(Error::NotDir {
+                line,
+                path: fs.path(id),
+            }),
+        },
+        None => Ok(fs.mkdir(dir, name)),
     }
 }
 
-fn get_total_small(fs: &Fs) -> usize {
-    match fs {
-        Fs::Dir(dir) => {
-            let size = dir.values().map(get_size).sum();
-            let total_small: usize = dir.values().map(get_total_small).sum();
-            total_small + if size <= 100000 { size } else { 0 }
-        }
-        Fs::File(_) => 0,
-    }
+pub fn puzzle1(input: &str) -> Result<usize, Error> {
+    let fs = filesystem(input)?;
+    let query = Query {
+        dirs: true,
+        max: Some(100000),
+        ..Default::default()
+    };
+    Ok(fs.find(0, &query).iter().map(|&id| fs.node(id).size).sum())
 }
 
-pub fn puzzle1(input: &str) -> usize {
-    let cmds = parse(input);
-    let mut root = HashMap::new();
-    explore(&mut root, &mut cmds.into_iter());
-    get_total_small(&Fs::Dir(root))
+pub struct Deletion {
+    pub used: usize,
+    pub need: usize,
+    // every directory big enough, smallest first, so the first one is chosen
+    pub candidates: Vec<usize>,
 }
 
-fn get_smallest(fs: &Fs, need: usize) -> Option<usize> {
-    match fs {
-        Fs::Dir(dir) => dir
-            .values()
-            .filter_map(|child| get_smallest(child, need))
-            .min()
-            .or({
-                let size = get_size(fs);
-                if size >= need {
-                    Some(size)
-                } else {
-                    None
-                }
-            }),
-        Fs::File(_) => None,
+pub fn deletion(fs: &Fs) -> Deletion {
+    let used = fs.node(0).size;
+    let need = (30000000 + used).saturating_sub(70000000);
+    let query = Query {
+        dirs: true,
+        min: Some(need),
+        ..Default::default()
+    };
+    let mut candidates = fs.find(0, &query);
+    candidates.sort_by_key(|&id| fs.node(id).size);
+    Deletion {
+        used,
+        need,
+        candidates,
     }
 }
 
-pub fn puzzle2(input: &str) -> usize {
-    let cmds = parse(input);
-    let mut root = HashMap::new();
-    explore(&mut root, &mut cmds.into_iter());
-    let fs = Fs::Dir(root);
-    let total = get_size(&fs);
-    get_smallest(&fs, 30000000 - (70000000 - total)).unwrap()
+pub fn puzzle2(input: &str) -> Result<usize, Error> {
+    let fs = filesystem(input)?;
+    Ok(fs.node(deletion(&fs).candidates[0]).size)
 }
 
 #[cfg(test)]
@@ -142,21 +501,145 @@ mod tests {
 
     #[test]
     fn test_puzzle1_example() {
-        assert_eq!(puzzle1(EXAMPLE), 95437);
+        assert_eq!(puzzle1(EXAMPLE), Ok(95437));
     }
 
     #[test]
     fn test_puzzle1_input() {
-        assert_eq!(puzzle1(INPUT), 1306611);
+        assert_eq!(puzzle1(INPUT), Ok(1306611));
     }
 
     #[test]
     fn test_puzzle2_example() {
-        assert_eq!(puzzle2(EXAMPLE), 24933642);
+        assert_eq!(puzzle2(EXAMPLE), Ok(24933642));
     }
 
     #[test]
     fn test_puzzle2_input() {
-        assert_eq!(puzzle2(INPUT), 13210366);
+        assert_eq!(puzzle2(INPUT), Ok(13210366));
+    }
+
+    #[test]
+    fn test_transcript() {
+        let input = "\
+$ ls
+dir a
+$ cd a/b
+$ ls
+10 x
+$ pwd
+/a/b
+$ cd /
+$ ls
+dir a
+5 y
+$ cd a
+$ cd ./b/..
+$ ls
+dir b
+";
+        let fs = filesystem(input).unwrap();
+        assert_eq!(fs.node(0).size, 15);
+        assert_eq!(fs.node(fs.lookup("/a").unwrap()).size, 10);
+    }
+
+    #[test]
+    fn test_transcript_errors() {
+        let error = |input| filesystem(input).err().unwrap();
+        assert_eq!(
+            error("$ ls\n1 a\n$ ls\n2 a"),
+            Error::SizeMismatch {
+                line: 4,
+                path: "/a".to_string(),
+                old: 1,
+                new: 2,
+            }
+        );
+        assert_eq!(
+            error("$ ls\n1 a\n$ cd a"),
+            Error::NotDir {
+                line: 3,
+                path: "/a".to_string(),
+            }
+        );
+        assert_eq!(error("$ cd /\n$ cd .."), Error::AboveRoot { line: 2 });
+        assert_eq!(error("$ cd /\ndir a"), Error::NoLs { line: 2 });
+        assert_eq!(error("$ ls\nx a"), Error::BadLine { line: 2 });
+    }
+
+    #[test]
+    fn test_tree_example() {
+        let fs = filesystem(EXAMPLE).unwrap();
+        assert_eq!(
+            fs.tree(fs.lookup("/a").unwrap()),
+            "\
+- a (dir, size=94853)
+  - e (dir, size=584)
+    - i (file, size=584)
+  - f (file, size=29116)
+  - g (file, size=2557)
+  - h.lst (file, size=62596)
+",
+        );
+        assert_eq!(fs.du(0), "46M\t/\n24M\t/d\n93K\t/a\n584\t/a/e\n");
+        assert_eq!(
+            fs.json(fs.lookup("/a/e").unwrap()),
+            r#"{"name":"e","type":"dir","size":584,"children":[{"name":"i","type":"file","size":584}]}"#,
+        );
+    }
+
+    #[test]
+    fn test_transcript_example() {
+        let fs = filesystem(EXAMPLE).unwrap();
+        let transcript = fs.transcript();
+        assert_eq!(puzzle1(&transcript), Ok(95437));
+        assert_eq!(puzzle2(&transcript), Ok(24933642));
+    }
+
+    #[test]
+    fn test_scan() {
+        let root = std::env::temp_dir().join(format!("aoc2022-day07-{}", std::process::id()));
+        stdfs::create_dir_all(root.join("a/e")).unwrap();
+        stdfs::create_dir_all(root.join("d")).unwrap();
+        stdfs::write(root.join("b.txt"), vec![0; 150000]).unwrap();
+        stdfs::write(root.join("a/f"), vec![0; 29116]).unwrap();
+        stdfs::write(root.join("a/e/i"), vec![0; 584]).unwrap();
+        stdfs::write(root.join("d/j"), vec![0; 40000]).unwrap();
+        let fs = Fs::scan(&root);
+        stdfs::remove_dir_all(&root).unwrap();
+
+        let transcript = fs.unwrap().transcript();
+        // `du --apparent-size --block-size=1` gives 29700 for a, 584 for a/e and 40000 for d
+        assert_eq!(puzzle1(&transcript), Ok(29700 + 584 + 40000));
+        assert_eq!(puzzle2(&transcript), Ok(584));
+    }
+
+    #[test]
+    fn test_lookup_example() {
+        let fs = filesystem(EXAMPLE).unwrap();
+        let e = fs.lookup("/a/e").unwrap();
+        assert_eq!(fs.node(e).size, 584);
+        assert_eq!(fs.path(e), "/a/e");
+        assert_eq!(fs.lookup("/"), Some(0));
+        assert_eq!(fs.lookup("/a/x"), None);
+    }
+
+    #[test]
+    fn test_find_example() {
+        let fs = filesystem(EXAMPLE).unwrap();
+        let query = Query {
+            files: true,
+            min: Some(5000000),
+            name: Some("*.*".to_string()),
+            ..Default::default()
+        };
+        let paths = fs
+            .find(0, &query)
+            .iter()
+            .map(|&id| fs.path(id))
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["/b.txt", "/c.dat", "/d/d.ext", "/d/d.log"]);
+        let d = fs.lookup("/d").unwrap();
+        assert_eq!(fs.find(d, &query).len(), 2);
     }
 }
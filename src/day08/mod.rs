@@ -1,4 +1,4 @@
-fn parse(input: &str) -> Vec<Vec<i8>> {
+pub fn parse(input: &str) -> Vec<Vec<i8>> {
     input
         .lines()
         .map(|line| {
@@ -9,101 +9,141 @@ fn parse(input: &str) -> Vec<Vec<i8>> {
         .collect()
 }
 
-pub fn puzzle1(input: &str) -> u32 {
-    let grid = parse(input);
-    let mut visible = vec![vec![false; grid[0].len()]; grid.len()];
-
-    // up
-    for j in 0..grid[0].len() {
-        let mut tallest = -1;
-        for i in 0..grid.len() {
-            if grid[i][j] > tallest {
-                visible[i][j] = true;
+// looking along each row and column in both directions, with a stack of the trees that aren't yet
+// hidden behind a taller one, gives visibility and viewing distance in linear time
+fn sweep(grid: &[Vec<i8>]) -> (Vec<Vec<bool>>, Vec<Vec<u64>>) {
+    let h = grid.len();
+    let w = grid.first().map_or(0, |row| row.len());
+    let mut visible = vec![vec![false; w]; h];
+    let mut scenic = vec![vec![1; w]; h];
+    let rows = (0..h).map(|i| (0..w).map(|j| (i, j)).collect::<Vec<_>>());
+    let cols = (0..w).map(|j| (0..h).map(|i| (i, j)).collect::<Vec<_>>());
+    for mut line in rows.chain(cols) {
+        for _ in 0..2 {
+            let mut stack: Vec<usize> = vec![];
+            for (k, &(i, j)) in line.iter().enumerate() {
+                while let Some(&top) = stack.last() {
+                    let (y, x) = line[top];
+                    if grid[y][x] >= grid[i][j] {
+                        break;
+                    }
+                    stack.pop();
+                }
+                match stack.last() {
+                    None => {
+                        visible[i][j] = true;
+                        scenic[i][j] *= k as u64;
+                    }
+                    Some(&top) => scenic[i][j] *= (k - top) as u64,
+                }
+                stack.push(k);
             }
-            tallest = tallest.max(grid[i][j]);
+            line.reverse();
         }
     }
+    (visible, scenic)
+}
 
-    // down
-    for j in 0..grid[0].len() {
-        let mut tallest = -1;
-        for i in (0..grid.len()).rev() {
-            if grid[i][j] > tallest {
-                visible[i][j] = true;
-            }
-            tallest = tallest.max(grid[i][j]);
+pub fn visibility(grid: &[Vec<i8>]) -> Vec<Vec<bool>> {
+    sweep(grid).0
+}
+
+pub fn scenic_scores(grid: &[Vec<i8>]) -> Vec<Vec<u64>> {
+    sweep(grid).1
+}
+
+pub enum Mode {
+    Height,
+    Visible,
+    Scenic,
+}
+
+impl Mode {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "height" => Some(Mode::Height),
+            "visible" => Some(Mode::Visible),
+            "scenic" => Some(Mode::Scenic),
+            _ => None,
         }
     }
+}
 
-    // left
-    for i in 0..grid.len() {
-        let mut tallest = -1;
-        for j in 0..grid[i].len() {
-            if grid[i][j] > tallest {
-                visible[i][j] = true;
-            }
-            tallest = tallest.max(grid[i][j]);
+// from dark blue at 0 to yellow at 1
+fn gradient(t: f64) -> (u8, u8, u8) {
+    let t = t.clamp(0.0, 1.0);
+    let mix = |a: f64, b: f64| (a + (b - a) * t).round() as u8;
+    (mix(20.0, 250.0), mix(30.0, 230.0), mix(120.0, 40.0))
+}
+
+// the tree with the best scenic score is always red
+fn colors(grid: &[Vec<i8>], mode: Mode) -> Vec<Vec<(u8, u8, u8)>> {
+    let (visible, scenic) = sweep(grid);
+    let max = scenic.iter().flatten().copied().max().unwrap_or(0);
+    let best = scenic
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| row.iter().enumerate().map(move |(j, &s)| (s, i, j)))
+        .find(|&(s, _, _)| s == max)
+        .map(|(_, i, j)| (i, j));
+    (0..grid.len())
+        .map(|i| {
+            (0..grid[i].len())
+                .map(|j| match mode {
+                    _ if Some((i, j)) == best => (220, 30, 30),
+                    Mode::Height => gradient(grid[i][j] as f64 / 9.0),
+                    Mode::Visible if visible[i][j] => (60, 200, 80),
+                    Mode::Visible => (40, 40, 40),
+                    Mode::Scenic => {
+                        gradient((scenic[i][j] as f64).ln_1p() / (max as f64).ln_1p().max(1.0))
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// two spaces per tree with a 24-bit background color
+pub fn heatmap(grid: &[Vec<i8>], mode: Mode) -> String {
+    let mut out = String::new();
+    for row in colors(grid, mode) {
+        for (r, g, b) in row {
+            out += &format!("\x1b[48;2;{r};{g};{b}m  ");
         }
+        out += "\x1b[0m\n";
     }
+    out
+}
 
-    // right
-    for i in 0..grid.len() {
-        let mut tallest = -1;
-        for j in (0..grid[i].len()).rev() {
-            if grid[i][j] > tallest {
-                visible[i][j] = true;
-            }
-            tallest = tallest.max(grid[i][j]);
-        }
+// plain PPM image, one pixel per tree
+pub fn ppm(grid: &[Vec<i8>], mode: Mode) -> String {
+    let colors = colors(grid, mode);
+    let w = colors.first().map_or(0, |row| row.len());
+    let mut out = format!("P3\n{} {}\n255\n", w, colors.len());
+    for row in colors {
+        let pixels = row
+            .into_iter()
+            .map(|(r, g, b)| format!("{r} {g} {b}"))
+            .collect::<Vec<_>>();
+        out += &pixels.join(" ");
+        out += "\n";
     }
+    out
+}
 
-    visible
+pub fn puzzle1(input: &str) -> u32 {
+    visibility(&parse(input))
         .into_iter()
         .map(|v| v.into_iter().map(|x| if x { 1 } else { 0 }).sum::<u32>())
         .sum()
 }
 
-pub fn puzzle2(input: &str) -> u32 {
-    let grid = parse(input);
-    let mut scenic = 0;
-    for i in 0..grid.len() {
-        for j in 0..grid[i].len() {
-            let mut up = 0;
-            for k in (0..i).rev() {
-                up += 1;
-                if grid[i][j] <= grid[k][j] {
-                    break;
-                }
-            }
-
-            let mut down = 0;
-            for k in (i + 1)..grid.len() {
-                down += 1;
-                if grid[i][j] <= grid[k][j] {
-                    break;
-                }
-            }
-
-            let mut left = 0;
-            for k in (0..j).rev() {
-                left += 1;
-                if grid[i][j] <= grid[i][k] {
-                    break;
-                }
-            }
-
-            let mut right = 0;
-            for k in (j + 1)..grid[i].len() {
-                right += 1;
-                if grid[i][j] <= grid[i][k] {
-                    break;
-                }
-            }
-
-            scenic = scenic.max(up * down * left * right);
-        }
-    }
-    scenic
+pub fn puzzle2(input: &str) -> u64 {
+    scenic_scores(&parse(input))
+        .into_iter()
+        .flatten()
+        .max()
+        .unwrap()
 }
 
 #[cfg(test)]
@@ -132,4 +172,88 @@ mod tests {
     fn test_puzzle2_input() {
         assert_eq!(puzzle2(INPUT), 392080);
     }
+
+    #[test]
+    fn test_scenic_scores_example() {
+        let scores = scenic_scores(&parse(EXAMPLE));
+        assert_eq!(scores[1][2], 4);
+        assert_eq!(scores[3][2], 8);
+        assert_eq!(scores[0], vec![0; 5]);
+    }
+
+    #[test]
+    fn test_ppm_example() {
+        let image = ppm(&parse(EXAMPLE), Mode::Visible);
+        let mut lines = image.lines();
+        assert_eq!(lines.next(), Some("P3"));
+        assert_eq!(lines.next(), Some("5 5"));
+        assert_eq!(
+            lines.nth(4),
+            Some("60 200 80 40 40 40 220 30 30 40 40 40 60 200 80")
+        );
+    }
+
+    // whether a tree can see past the trees in one direction, and how many of them it sees
+    fn look(tree: i8, line: impl Iterator<Item = i8>) -> (bool, u64) {
+        let mut seen = 0;
+        for t in line {
+            seen += 1;
+            if t >= tree {
+                return (false, seen);
+            }
+        }
+        (true, seen)
+    }
+
+    // the straightforward scan in all four directions from every tree
+    fn brute_force(grid: &[Vec<i8>]) -> (Vec<Vec<bool>>, Vec<Vec<u64>>) {
+        let (h, w) = (grid.len(), grid[0].len());
+        let mut visible = vec![vec![false; w]; h];
+        let mut scenic = vec![vec![1; w]; h];
+        for i in 0..h {
+            for j in 0..w {
+                let tree = grid[i][j];
+                for (clear, seen) in [
+                    look(tree, (0..i).rev().map(|k| grid[k][j])),
+                    look(tree, (i + 1..h).map(|k| grid[k][j])),
+                    look(tree, (0..j).rev().map(|k| grid[i][k])),
+                    look(tree, (j + 1..w).map(|k| grid[i][k])),
+                ] {
+                    visible[i][j] |= clear;
+                    scenic[i][j] *= seen;
+                }
+            }
+        }
+        (visible, scenic)
+    }
+
+    #[test]
+    fn test_large_forest() {
+        let n = 1000;
+        let mut seed = 1u64;
+        let grid = (0..n)
+            .map(|_| {
+                (0..n)
+                    .map(|_| {
+                        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+                        ((seed >> 33) % 10) as i8
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let (visible, scores) = brute_force(&grid);
+        assert_eq!(visibility(&grid), visible);
+        assert_eq!(scenic_scores(&grid), scores);
+        assert_eq!(brute_force(&parse(EXAMPLE)), sweep(&parse(EXAMPLE)));
+    }
+
+    #[test]
+    fn test_tall_tree_in_flat_forest() {
+        let n = 601;
+        let mut grid = vec![vec![0; n]; n];
+        grid[n / 2][n / 2] = 9;
+        let scores = scenic_scores(&grid);
+        assert_eq!(scores[n / 2][n / 2], 300u64.pow(4));
+        assert_eq!(scores, brute_force(&grid).1);
+    }
 }
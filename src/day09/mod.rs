@@ -1,46 +1,69 @@
 use std::collections::HashSet;
 
-use itertools::Itertools;
-
-#[derive(Clone, Copy, Eq, Hash, PartialEq)]
-struct Pos {
-    x: i32,
-    y: i32,
-}
-
-#[derive(Clone, Copy)]
-enum Dir {
-    U,
-    D,
-    L,
-    R,
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Pos {
+    pub x: i32,
+    pub y: i32,
 }
 
-use Dir::*;
-
-fn parse(input: &str) -> impl Iterator<Item = (Dir, i32)> + '_ {
+// each line is one or more direction and distance pairs, like `R 4`, `UL 2` or `R 3 U 1`; the
+// head moves one unit along every axis that still has distance left, so `R 3 U 1` is one
+// diagonal step followed by two steps right; each direction may appear at most once on a line,
+// and opposing directions not at all
+fn parse(input: &str) -> impl Iterator<Item = Pos> + '_ {
     input.lines().map(|line| {
-        let (d, n) = line.split_whitespace().collect_tuple().unwrap();
-        (
-            match d {
-                "U" => U,
-                "D" => D,
-                "L" => L,
-                "R" => R,
-                _ => panic!(),
-            },
-            n.parse().unwrap(),
-        )
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        assert!(words.len() % 2 == 0, "bad motion {:?}", line);
+        let mut total = Pos { x: 0, y: 0 };
+        let mut seen = vec![];
+        for pair in words.chunks(2) {
+            let n: i32 = pair[1].parse().unwrap();
+            for c in pair[0].chars() {
+                let (axis, d, opposite) = match c {
+                    'U' => (&mut total.y, n, 'D'),
+                    'D' => (&mut total.y, -n, 'U'),
+                    'L' => (&mut total.x, -n, 'R'),
+                    'R' => (&mut total.x, n, 'L'),
+                    _ => panic!("bad direction {:?}", pair[0]),
+                };
+                assert!(
+                    !seen.contains(&c),
+                    "repeated direction in motion {:?}",
+                    line
+                );
+                assert!(
+                    !seen.contains(&opposite),
+                    "opposing directions in motion {:?}",
+                    line
+                );
+                seen.push(c);
+                *axis += d;
+            }
+        }
+        total
     })
 }
 
-fn motion(d: Dir, h: &mut Pos) {
-    match d {
-        U => h.y += 1,
-        D => h.y -= 1,
-        L => h.x -= 1,
-        R => h.x += 1,
+// unit moves for the head
+pub fn steps(input: &str) -> Vec<Pos> {
+    let mut steps = vec![];
+    for mut left in parse(input) {
+        while left.x != 0 || left.y != 0 {
+            let d = Pos {
+                x: left.x.signum(),
+                y: left.y.signum(),
+            };
+            left.x -= d.x;
+            left.y -= d.y;
+            steps.push(d);
+        }
     }
+    steps
+}
+
+fn motion(d: Pos, h: &mut Pos) {
+    h.x += d.x;
+    h.y += d.y;
 }
 
 fn follow(h: Pos, t: &mut Pos) {
@@ -58,27 +81,145 @@ fn follow(h: Pos, t: &mut Pos) {
     }
 }
 
-fn solve<const L: usize>(input: &str) -> usize {
-    let mut positions = HashSet::new();
-    let mut rope = [Pos { x: 0, y: 0 }; L];
-    for (d, n) in parse(input) {
-        for _ in 0..n {
-            motion(d, &mut rope[0]);
-            for i in 1..L {
-                follow(rope[i - 1], &mut rope[i]);
-            }
-            positions.insert(rope[L - 1]);
+pub struct Rope {
+    knots: Vec<Pos>,
+    // every position each knot has been in, including the start
+    visited: Vec<HashSet<Pos>>,
+}
+
+impl Rope {
+    pub fn new(n: usize) -> Self {
+        assert!(n > 0, "a rope needs at least one knot");
+        let start = Pos { x: 0, y: 0 };
+        Rope {
+            knots: vec![start; n],
+            visited: vec![HashSet::from([start]); n],
+        }
+    }
+
+    // the head is first and the tail is last
+    pub fn knots(&self) -> &[Pos] {
+        &self.knots
+    }
+
+    pub fn visited(&self, knot: usize) -> &HashSet<Pos> {
+        &self.visited[knot]
+    }
+
+    pub fn step(&mut self, d: Pos) {
+        motion(d, &mut self.knots[0]);
+        self.visited[0].insert(self.knots[0]);
+        for i in 1..self.knots.len() {
+            follow(self.knots[i - 1], &mut self.knots[i]);
+            self.visited[i].insert(self.knots[i]);
+        }
+    }
+}
+
+pub fn simulate(input: &str, n: usize) -> Rope {
+    let mut rope = Rope::new(n);
+    for d in steps(input) {
+        rope.step(d);
+    }
+    rope
+}
+
+// the knots after every step, starting with them all at the origin
+pub fn trace(input: &str, n: usize) -> Vec<Vec<Pos>> {
+    let mut rope = Rope::new(n);
+    let mut states = vec![rope.knots().to_vec()];
+    for d in steps(input) {
+        rope.step(d);
+        states.push(rope.knots().to_vec());
+    }
+    states
+}
+
+pub fn csv(trace: &[Vec<Pos>]) -> String {
+    let mut out = "step,knot,x,y\n".to_string();
+    for (step, knots) in trace.iter().enumerate() {
+        for (knot, Pos { x, y }) in knots.iter().enumerate() {
+            out += &format!("{step},{knot},{x},{y}\n");
+        }
+    }
+    out
+}
+
+// the smallest rectangle containing the origin and all the given positions
+fn bounds<'a>(positions: impl IntoIterator<Item = &'a Pos>) -> (Pos, Pos) {
+    let origin = Pos { x: 0, y: 0 };
+    positions.into_iter().fold((origin, origin), |(lo, hi), p| {
+        (
+            Pos {
+                x: lo.x.min(p.x),
+                y: lo.y.min(p.y),
+            },
+            Pos {
+                x: hi.x.max(p.x),
+                y: hi.y.max(p.y),
+            },
+        )
+    })
+}
+
+// like the diagrams in the puzzle, with `y` increasing upwards
+fn render((lo, hi): (Pos, Pos), cell: impl Fn(Pos) -> char) -> String {
+    let mut out = String::new();
+    for y in (lo.y..=hi.y).rev() {
+        for x in lo.x..=hi.x {
+            out.push(cell(Pos { x, y }));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub fn render_visited(visited: &HashSet<Pos>) -> String {
+    render(bounds(visited), |p| {
+        if p == (Pos { x: 0, y: 0 }) {
+            's'
+        } else if visited.contains(&p) {
+            '#'
+        } else {
+            '.'
         }
+    })
+}
+
+// `H` for the head, then `1` through `9` and so on, or `T` for the tail of a two-knot rope
+fn label(knot: usize, n: usize) -> char {
+    match knot {
+        0 => 'H',
+        1 if n == 2 => 'T',
+        _ => char::from_digit(knot as u32 % 36, 36).unwrap(),
     }
-    positions.len()
+}
+
+// one frame per step, all drawn within the same bounds
+pub fn animate(trace: &[Vec<Pos>]) -> Vec<String> {
+    let b = bounds(trace.iter().flatten());
+    trace
+        .iter()
+        .map(|knots| {
+            render(b, |p| match knots.iter().position(|&k| k == p) {
+                Some(i) => label(i, knots.len()),
+                None if p == (Pos { x: 0, y: 0 }) => 's',
+                None => '.',
+            })
+        })
+        .collect()
+}
+
+fn solve(input: &str, n: usize) -> usize {
+    simulate(input, n).visited(n - 1).len()
 }
 
 pub fn puzzle1(input: &str) -> usize {
-    solve::<2>(input)
+    solve(input, 2)
 }
 
 pub fn puzzle2(input: &str) -> usize {
-    solve::<10>(input)
+    solve(input, 10)
 }
 
 #[cfg(test)]
@@ -108,4 +249,84 @@ mod tests {
     fn test_puzzle2_input() {
         assert_eq!(puzzle2(INPUT), 2541);
     }
+
+    #[test]
+    fn test_rope_example2() {
+        let rope = simulate(EXAMPLE2, 10);
+        assert_eq!(rope.knots()[0], Pos { x: -11, y: 15 });
+        assert_eq!(rope.visited(9).len(), 36);
+        assert_eq!(
+            rope.visited(1).len(),
+            simulate(EXAMPLE2, 2).visited(1).len()
+        );
+    }
+
+    #[test]
+    fn test_render_example2() {
+        let rope = simulate(EXAMPLE2, 10);
+        assert_eq!(
+            render_visited(rope.visited(9)),
+            "\
+#.....................
+#.............###.....
+#............#...#....
+.#..........#.....#...
+..#..........#.....#..
+...#........#.......#.
+....#......s.........#
+.....#..............#.
+......#............#..
+.......#..........#...
+........#........#....
+.........########.....
+",
+        );
+    }
+
+    #[test]
+    fn test_animate_example1() {
+        let trace = trace(EXAMPLE1, 2);
+        let frames = animate(&trace);
+        assert_eq!(frames.len(), 25);
+        assert_eq!(frames[4], "......\n......\n......\n......\ns..TH.\n");
+        assert_eq!(
+            csv(&trace[..2]),
+            "step,knot,x,y\n0,0,0,0\n0,1,0,0\n1,0,1,0\n1,1,0,0\n",
+        );
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        assert_eq!(steps("UR 2"), steps("R 1 U 1\nU 1 R 1"));
+        assert_eq!(
+            steps("R 3 U 1"),
+            vec![Pos { x: 1, y: 1 }, Pos { x: 1, y: 0 }, Pos { x: 1, y: 0 }],
+        );
+        let rope = simulate("UR 3", 2);
+        assert_eq!(rope.knots(), [Pos { x: 3, y: 3 }, Pos { x: 2, y: 2 }]);
+    }
+
+    #[test]
+    #[should_panic(expected = "opposing directions")]
+    fn test_opposing_moves() {
+        steps("R 3 L 1");
+    }
+
+    #[test]
+    #[should_panic(expected = "opposing directions")]
+    fn test_opposing_diagonal() {
+        steps("RL 5");
+    }
+
+    #[test]
+    #[should_panic(expected = "repeated direction")]
+    fn test_repeated_direction() {
+        steps("UU 2");
+    }
+
+    #[test]
+    #[should_panic(expected = "repeated direction")]
+    fn test_repeated_pair() {
+        steps("R 2 R 3");
+    }
 }
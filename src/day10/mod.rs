@@ -1,111 +1,606 @@
-enum Instr {
-    Addx(i32),
-    Noop,
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fmt,
+};
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnknownOpcode { line: usize, opcode: String },
+    WrongOperands { line: usize, expected: usize },
+    BadOperand { line: usize, operand: String },
+    TooManyCycles { limit: usize },
+    Undrawable { x: usize, y: usize },
+    Overflow { line: usize },
+    SignalOverflow { cycle: usize },
+    Ragged { row: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownOpcode { line, opcode } => {
+                write!(f, "line {line}: unknown opcode {opcode:?}")
+            }
+            Error::WrongOperands { line, expected } => {
+                write!(f, "line {line}: expected {expected} operands")
+            }
+            Error::BadOperand { line, operand } => {
+                write!(f, "line {line}: bad operand {operand:?}")
+            }
+            Error::TooManyCycles { limit } => write!(f, "still running after {limit} cycles"),
+            Error::Undrawable { x, y } => write!(f, "no program can draw pixel {x},{y}"),
+            Error::Overflow { line } => write!(f, "line {line}: register overflow"),
+            Error::Ragged { row } => write!(f, "row {row} is a different width"),
+            Error::SignalOverflow { cycle } => {
+                write!(f, "signal strength sum overflows at cycle {cycle}")
+            }
+        }
+    }
 }
 
-use Instr::*;
+// registers are named `a` through `z`
+pub type Registers = [i32; 26];
+
+pub const X: usize = (b'x' - b'a') as usize;
 
-fn parse(input: &str) -> impl Iterator<Item = Instr> + '_ {
-    input.lines().map(|line| {
-        let mut words = line.split_whitespace();
-        match words.next().unwrap() {
-            "addx" => Addx(words.next().unwrap().parse().unwrap()),
-            "noop" => Noop,
-            _ => panic!(),
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Reg(usize),
+    Imm(i32),
+}
+
+impl Operand {
+    fn get(self, regs: &Registers) -> i32 {
+        match self {
+            Operand::Reg(r) => regs[r],
+            Operand::Imm(v) => v,
         }
+    }
+}
+
+// jumps are relative to the jump itself
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Noop,
+    Addx(i32),
+    Set(usize, Operand),
+    Add(usize, Operand),
+    Mul(usize, Operand),
+    Jmp(i32),
+    Jnz(Operand, i32),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Instr {
+    pub op: Op,
+    pub cycles: usize,
+    // one-based, for errors
+    pub line: usize,
+}
+
+// opcode, cycles and number of operands
+const OPCODES: &[(&str, usize, usize)] = &[
+    ("noop", 1, 0),
+    ("addx", 2, 1),
+    ("set", 1, 2),
+    ("add", 2, 2),
+    ("mul", 2, 2),
+    ("jmp", 1, 1),
+    ("jnz", 2, 2),
+];
+
+fn parse_reg(line: usize, s: &str) -> Result<usize, Error> {
+    match s.as_bytes() {
+        &[c @ b'a'..=b'z'] => Ok((c - b'a') as usize),
+        _ => Err(Error::BadOperand {
+            line,
+            operand: s.to_string(),
+        }),
+    }
+}
+
+fn parse_imm(line: usize, s: &str) -> Result<i32, Error> {
+    s.parse().map_err(|_| Error::BadOperand {
+        line,
+        operand: s.to_string(),
     })
 }
 
-trait State {
-    fn addx(&mut self, v: i32);
-    fn step(&mut self);
+fn parse_operand(line: usize, s: &str) -> Result<Operand, Error> {
+    match parse_reg(line, s) {
+        Ok(r) => Ok(Operand::Reg(r)),
+        Err(_) => Ok(Operand::Imm(parse_imm(line, s)?)),
+    }
+}
+
+pub fn parse(input: &str) -> Result<Vec<Instr>, Error> {
+    let mut program = vec![];
+    for (i, text) in input.lines().enumerate() {
+        let line = i + 1;
+        let words = text.split_whitespace().collect::<Vec<_>>();
+        let Some((&opcode, args)) = words.split_first() else {
+            continue;
+        };
+        let &(_, cycles, arity) = OPCODES
+            .iter()
+            .find(|&&(name, _, _)| name == opcode)
+            .ok_or_else(|| Error::UnknownOpcode {
+                line,
+                opcode: opcode.to_string(),
+            })?;
+        if args.len() != arity {
+            return Err(Error::WrongOperands {
+                line,
+                expected: arity,
+            });
+        }
+        let op = match opcode {
+            "noop" => Op::Noop,
+            "addx" => Op::Addx(parse_imm(line, args[0])?),
+            "set" => Op::Set(parse_reg(line, args[0])?, parse_operand(line, args[1])?),
+            "add" => Op::Add(parse_reg(line, args[0])?, parse_operand(line, args[1])?),
+            "mul" => Op::Mul(parse_reg(line, args[0])?, parse_operand(line, args[1])?),
+            "jmp" => Op::Jmp(parse_imm(line, args[0])?),
+            "jnz" => Op::Jnz(parse_operand(line, args[0])?, parse_imm(line, args[1])?),
+            _ => unreachable!(),
+        };
+        program.push(Instr { op, cycles, line });
+    }
+    Ok(program)
+}
+
+// observes the registers during every cycle, before any instruction finishing in it takes effect
+pub trait State {
+    fn step(&mut self, cycle: usize, regs: &Registers);
+}
+
+#[derive(Clone)]
+pub struct Cpu {
+    pub regs: Registers,
+    // index of the current instruction
+    pub pc: usize,
+    // number of cycles completed so far
+    pub cycle: usize,
+    // number of cycles already spent on the current instruction
+    pub busy: usize,
 }
 
-fn run(instrs: impl Iterator<Item = Instr>, state: &mut impl State) {
-    for instr in instrs {
-        match instr {
-            Addx(v) => {
-                state.step();
-                state.step();
-                state.addx(v);
+impl Cpu {
+    pub fn new() -> Self {
+        let mut regs = [0; 26];
+        regs[X] = 1;
+        Cpu {
+            regs,
+            pc: 0,
+            cycle: 0,
+            busy: 0,
+        }
+    }
+
+    pub fn halted(&self, program: &[Instr]) -> bool {
+        self.pc >= program.len()
+    }
+
+    // one cycle, which might finish an instruction
+    pub fn tick(&mut self, program: &[Instr], state: &mut impl State) -> Result<(), Error> {
+        let Instr { op, cycles, line } = program[self.pc];
+        self.cycle += 1;
+        state.step(self.cycle, &self.regs);
+        self.busy += 1;
+        if self.busy < cycles {
+            return Ok(());
+        }
+        self.busy = 0;
+        let mut next = self.pc as i64 + 1;
+        let overflow = Error::Overflow { line };
+        match op {
+            Op::Noop => {}
+            Op::Addx(v) => self.regs[X] = self.regs[X].checked_add(v).ok_or(overflow)?,
+            Op::Set(r, v) => self.regs[r] = v.get(&self.regs),
+            Op::Add(r, v) => {
+                self.regs[r] = self.regs[r]
+                    .checked_add(v.get(&self.regs))
+                    .ok_or(overflow)?
             }
-            Noop => {
-                state.step();
+            Op::Mul(r, v) => {
+                self.regs[r] = self.regs[r]
+                    .checked_mul(v.get(&self.regs))
+                    .ok_or(overflow)?
             }
+            Op::Jmp(offset) => next = self.pc as i64 + offset as i64,
+            Op::Jnz(v, offset) => {
+                if v.get(&self.regs) != 0 {
+                    next = self.pc as i64 + offset as i64;
+                }
+            }
+        }
+        // jumping before the start halts just like running off the end
+        self.pc = usize::try_from(next).unwrap_or(usize::MAX);
+        Ok(())
+    }
+}
+
+const LIMIT: usize = 1_000_000;
+
+fn run(program: &[Instr], state: &mut impl State) -> Result<(), Error> {
+    let mut cpu = Cpu::new();
+    while !cpu.halted(program) {
+        if cpu.cycle == LIMIT {
+            return Err(Error::TooManyCycles { limit: LIMIT });
         }
+        cpu.tick(program, state)?;
     }
+    Ok(())
 }
 
+// the cycle in which the sum overflowed, if it did
 struct State1 {
-    cycle: i32,
-    x: i32,
-    sum: i32,
+    sum: Result<i32, usize>,
 }
 
 impl State for State1 {
-    fn addx(&mut self, v: i32) {
-        self.x += v;
-    }
-
-    fn step(&mut self) {
-        if self.cycle % 40 == 20 {
-            self.sum += self.cycle * self.x;
+    fn step(&mut self, cycle: usize, regs: &Registers) {
+        if cycle % 40 == 20 {
+            self.sum = self.sum.and_then(|sum| {
+                i32::try_from(cycle)
+                    .ok()
+                    .and_then(|c| c.checked_mul(regs[X]))
+                    .and_then(|strength| sum.checked_add(strength))
+                    .ok_or(cycle)
+            });
         }
-        self.cycle += 1;
     }
 }
 
-pub fn puzzle1(input: &str) -> i32 {
-    let mut state = State1 {
-        cycle: 1,
-        x: 1,
-        sum: 0,
-    };
-    run(parse(input), &mut state);
-    state.sum
+pub fn puzzle1(input: &str) -> Result<i32, Error> {
+    let mut state = State1 { sum: Ok(0) };
+    run(&parse(input)?, &mut state)?;
+    state.sum.map_err(|cycle| Error::SignalOverflow { cycle })
 }
 
 const W: usize = 40;
 const H: usize = 6;
 
+#[derive(Clone)]
 struct State2 {
-    cycle: i32,
-    x: i32,
-    crt: [[bool; W]; H],
+    w: usize,
+    crt: Vec<Vec<bool>>,
 }
 
 impl State for State2 {
-    fn addx(&mut self, v: i32) {
-        self.x += v;
+    fn step(&mut self, cycle: usize, regs: &Registers) {
+        let w = self.w;
+        if w == 0 {
+            return;
+        }
+        let i = cycle - 1;
+        let x = i % w;
+        let y = i / w;
+        if y < self.crt.len() && (x as i32 - regs[X]).abs() <= 1 {
+            self.crt[y][x] = true;
+        }
     }
+}
 
-    fn step(&mut self) {
-        let i = self.cycle as usize - 1;
-        let x = i % W;
-        let y = i / W;
-        if (x as i32 - self.x).abs() <= 1 {
-            self.crt[y][x] = true;
+impl State2 {
+    fn new(w: usize, h: usize) -> Self {
+        State2 {
+            w,
+            crt: vec![vec![false; w]; h],
+        }
+    }
+
+    // undoes the pixel drawn in the cycle after `cycle` cycles
+    fn clear(&mut self, cycle: usize) {
+        let w = self.w;
+        if let Some(row) = self.crt.get_mut(cycle.checked_div(w).unwrap_or(usize::MAX)) {
+            row[cycle % w] = false;
         }
-        self.cycle += 1;
+    }
+
+    fn render(&self) -> String {
+        self.crt
+            .iter()
+            .flat_map(|row| row.iter().map(|&b| if b { '#' } else { '.' }).chain(['\n']))
+            .collect()
     }
 }
 
-pub fn puzzle2(input: &str) -> String {
-    let mut state = State2 {
-        cycle: 1,
-        x: 1,
-        crt: [[false; 40]; 6],
-    };
-    run(parse(input), &mut state);
-    state
-        .crt
-        .into_iter()
-        .flat_map(|row| {
-            row.into_iter()
-                .map(|b| if b { '#' } else { '.' })
-                .chain(['\n'])
+pub fn draw(program: &[Instr], w: usize, h: usize) -> Result<String, Error> {
+    let mut state = State2::new(w, h);
+    run(program, &mut state)?;
+    Ok(state.render())
+}
+
+pub fn puzzle2(input: &str) -> Result<String, Error> {
+    draw(&parse(input)?, W, H)
+}
+
+pub enum Break {
+    // just before this cycle starts
+    Cycle(usize),
+    // just before this instruction starts
+    Instr(usize),
+}
+
+pub enum Stop {
+    Halted,
+    Fault(Error),
+    Break,
+    Watch { old: i32, new: i32 },
+    Start,
+    // stepped back as far as the history goes
+    Forgotten,
+    // still running after `LIMIT` cycles
+    Limit,
+}
+
+// how many cycles can be stepped back
+const HISTORY: usize = 100_000;
+
+pub struct Debugger {
+    program: Vec<Instr>,
+    cpu: Cpu,
+    crt: State2,
+    // the CPU from before each recent cycle, for stepping backward; each cycle draws at most its
+    // own pixel for the first time, so the screen can be undone without saving it
+    history: VecDeque<Cpu>,
+    // the cycle a breakpoint was last reported at, so continuing from it doesn't stop again
+    reported: Option<usize>,
+    pub breakpoints: Vec<Break>,
+    pub watch: bool,
+}
+
+impl Debugger {
+    pub fn new(program: Vec<Instr>, w: usize, h: usize) -> Self {
+        Debugger {
+            program,
+            cpu: Cpu::new(),
+            crt: State2::new(w, h),
+            history: VecDeque::new(),
+            reported: None,
+            breakpoints: vec![],
+            watch: false,
+        }
+    }
+
+    fn at_break(&self) -> bool {
+        self.breakpoints.iter().any(|b| match *b {
+            Break::Cycle(n) => self.cpu.cycle + 1 == n,
+            Break::Instr(i) => self.cpu.pc == i && self.cpu.busy == 0,
         })
-        .collect()
+    }
+
+    pub fn step(&mut self) -> Option<Stop> {
+        if self.cpu.halted(&self.program) {
+            return Some(Stop::Halted);
+        }
+        if self.history.len() == HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(self.cpu.clone());
+        let old = self.cpu.regs[X];
+        if let Err(err) = self.cpu.tick(&self.program, &mut self.crt) {
+            self.back();
+            return Some(Stop::Fault(err));
+        }
+        let new = self.cpu.regs[X];
+        if self.watch && old != new {
+            return Some(Stop::Watch { old, new });
+        }
+        if self.cpu.halted(&self.program) {
+            return Some(Stop::Halted);
+        }
+        if self.at_break() {
+            self.reported = Some(self.cpu.cycle);
+            return Some(Stop::Break);
+        }
+        None
+    }
+
+    pub fn back(&mut self) -> Option<Stop> {
+        match self.history.pop_back() {
+            Some(cpu) => {
+                self.cpu = cpu;
+                self.crt.clear(self.cpu.cycle);
+                None
+            }
+            None if self.cpu.cycle == 0 => Some(Stop::Start),
+            None => Some(Stop::Forgotten),
+        }
+    }
+
+    pub fn cont(&mut self) -> Stop {
+        if self.at_break() && self.reported != Some(self.cpu.cycle) {
+            self.reported = Some(self.cpu.cycle);
+            return Stop::Break;
+        }
+        loop {
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+            if self.cpu.cycle >= LIMIT {
+                return Stop::Limit;
+            }
+        }
+    }
+
+    // the last cycle, the pixel it drew, and the next instruction
+    pub fn status(&self) -> String {
+        let mut out = format!("cycle {}, x = {}", self.cpu.cycle, self.cpu.regs[X]);
+        if let Some(before) = self.history.back() {
+            let i = self.cpu.cycle - 1;
+            let w = self.crt.w;
+            if w > 0 && i / w < self.crt.crt.len() {
+                let (col, row) = (i % w, i / w);
+                let lit = if self.crt.crt[row][col] {
+                    "lit"
+                } else {
+                    "dark"
+                };
+                let x = before.regs[X];
+                out += &format!(", drew {lit} pixel {col},{row} with sprite at {x}");
+            }
+        }
+        match self.program.get(self.cpu.pc) {
+            Some(instr) => {
+                out += &format!(
+                    "\nnext: instruction {} (line {}), {:?}, {} of {} cycles done",
+                    self.cpu.pc, instr.line, instr.op, self.cpu.busy, instr.cycles,
+                )
+            }
+            None => out += "\nhalted",
+        }
+        out
+    }
+
+    pub fn screen(&self) -> String {
+        self.crt.render()
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Operand::Reg(r) => write!(f, "{}", (b'a' + r as u8) as char),
+            Operand::Imm(v) => write!(f, "{v}"),
+        }
+    }
+}
+
+// the same syntax that `parse` reads
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reg = |r| Operand::Reg(r);
+        match self.op {
+            Op::Noop => write!(f, "noop"),
+            Op::Addx(v) => write!(f, "addx {v}"),
+            Op::Set(r, v) => write!(f, "set {} {v}", reg(r)),
+            Op::Add(r, v) => write!(f, "add {} {v}", reg(r)),
+            Op::Mul(r, v) => write!(f, "mul {} {v}", reg(r)),
+            Op::Jmp(offset) => write!(f, "jmp {offset}"),
+            Op::Jnz(v, offset) => write!(f, "jnz {v} {offset}"),
+        }
+    }
+}
+
+pub fn disassemble(program: &[Instr]) -> String {
+    program.iter().map(|instr| format!("{instr}\n")).collect()
+}
+
+// every row has to be as wide as the first
+fn check_width(image: &[Vec<bool>]) -> Result<(), Error> {
+    let w = image.first().map_or(0, |row| row.len());
+    match image.iter().position(|row| row.len() != w) {
+        Some(i) => Err(Error::Ragged { row: i + 1 }),
+        None => Ok(()),
+    }
+}
+
+pub fn bitmap(image: &str) -> Result<Vec<Vec<bool>>, Error> {
+    let image = image
+        .lines()
+        .map(|line| line.chars().map(|c| c == '#').collect())
+        .collect::<Vec<_>>();
+    check_width(&image)?;
+    Ok(image)
+}
+
+// the letters that show up in puzzle answers, each four pixels wide and six tall
+const FONT: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...", "#...", ".#.#", "..#.", "..#.", "..#."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+// each letter takes five columns, including a blank one after it
+pub fn typeset(text: &str, w: usize, h: usize) -> Option<Vec<Vec<bool>>> {
+    let mut image = vec![vec![false; w]; h];
+    for (k, c) in text.chars().enumerate() {
+        let (_, glyph) = FONT.iter().find(|&&(letter, _)| letter == c)?;
+        for (y, row) in glyph.iter().enumerate() {
+            for (dx, pixel) in row.chars().enumerate() {
+                *image.get_mut(y)?.get_mut(k * 5 + dx)? = pixel == '#';
+            }
+        }
+    }
+    Some(image)
+}
+
+// Only `addx` moves the sprite, and only after its second cycle, so a search over the sprite
+// position at each instruction boundary finds a program if there is one. The first two pixels
+// are always lit, since `x` starts at 1 and can't change until the third cycle.
+pub fn compile(image: &[Vec<bool>]) -> Result<Vec<Instr>, Error> {
+    check_width(image)?;
+    let w = image.first().map_or(0, |row| row.len());
+    let n = w * image.len();
+    // any sprite position further out than these is off the screen just the same
+    let (lo, hi) = (-2, w as i32 + 1);
+    let fits =
+        |t: usize, x: i32| t >= n || image[t / w][t % w] == (((t % w) as i32 - x).abs() <= 1);
+    // for each cycle boundary and sprite position, how it was reached
+    let mut parents = vec![BTreeMap::new(); n + 2];
+    parents[0].insert(1, None);
+    for t in 0..n {
+        let xs = parents[t].keys().copied().collect::<Vec<_>>();
+        if xs.is_empty() {
+            continue;
+        }
+        for x in xs {
+            if !fits(t, x) {
+                continue;
+            }
+            parents[t + 1].entry(x).or_insert(Some((x, Op::Noop)));
+            if fits(t + 1, x) {
+                for next in lo..=hi {
+                    parents[t + 2]
+                        .entry(next)
+                        .or_insert(Some((x, Op::Addx(next - x))));
+                }
+            }
+        }
+    }
+    let (mut t, mut x) = match (n..n + 2).find_map(|t| Some((t, *parents[t].keys().next()?))) {
+        Some(end) => end,
+        None => {
+            let stuck = (0..n).rev().find(|&t| !parents[t].is_empty()).unwrap_or(0);
+            return Err(Error::Undrawable {
+                x: stuck % w,
+                y: stuck / w,
+            });
+        }
+    };
+    let mut program = vec![];
+    while let Some((prev, op)) = parents[t][&x] {
+        let cycles = if let Op::Noop = op { 1 } else { 2 };
+        program.push(Instr {
+            op,
+            cycles,
+            line: 0,
+        });
+        t -= cycles;
+        x = prev;
+    }
+    program.reverse();
+    for (i, instr) in program.iter_mut().enumerate() {
+        instr.line = i + 1;
+    }
+    Ok(program)
 }
 
 #[cfg(test)]
@@ -119,21 +614,149 @@ mod tests {
 
     #[test]
     fn test_puzzle1_example() {
-        assert_eq!(puzzle1(EXAMPLE), 13140);
+        assert_eq!(puzzle1(EXAMPLE), Ok(13140));
     }
 
     #[test]
     fn test_puzzle1_input() {
-        assert_eq!(puzzle1(INPUT), 15220);
+        assert_eq!(puzzle1(INPUT), Ok(15220));
     }
 
     #[test]
     fn test_puzzle2_example() {
-        assert_eq!(puzzle2(EXAMPLE), IMAGE);
+        assert_eq!(puzzle2(EXAMPLE), Ok(IMAGE.to_string()));
     }
 
     #[test]
     fn test_puzzle2_input() {
-        assert_eq!(puzzle2(INPUT), LETTERS);
+        assert_eq!(puzzle2(INPUT), Ok(LETTERS.to_string()));
+    }
+
+    #[test]
+    fn test_loop() {
+        // x = 1 + 5 * 4 = 21 after the loop, then x stays put until cycle 40
+        let program = "set c 5\naddx 4\nadd c -1\njnz c -2\nnoop\njmp 2\naddx 100\nnoop";
+        struct Trace(Vec<i32>);
+        impl State for Trace {
+            fn step(&mut self, _: usize, regs: &Registers) {
+                self.0.push(regs[X]);
+            }
+        }
+        let mut trace = Trace(vec![]);
+        run(&parse(program).unwrap(), &mut trace).unwrap();
+        assert_eq!(trace.0.len(), 1 + 5 * 6 + 1 + 1 + 1);
+        assert_eq!(trace.0.last(), Some(&21));
+    }
+
+    #[test]
+    fn test_debugger_example() {
+        let mut debugger = Debugger::new(parse(EXAMPLE).unwrap(), W, H);
+        debugger.breakpoints.push(Break::Cycle(20));
+        assert!(matches!(debugger.cont(), Stop::Break));
+        assert_eq!(debugger.cpu.cycle, 19);
+        debugger.step();
+        assert_eq!(debugger.history.back().unwrap().regs[X], 21);
+        debugger.breakpoints.clear();
+        debugger.watch = true;
+        assert!(matches!(debugger.cont(), Stop::Watch { old: 21, new: 20 }));
+        while debugger.back().is_none() {}
+        assert_eq!(debugger.cpu.cycle, 0);
+        debugger.watch = false;
+        assert!(matches!(debugger.cont(), Stop::Halted));
+        assert_eq!(debugger.screen(), IMAGE);
+
+        let mut debugger = Debugger::new(parse(EXAMPLE).unwrap(), W, H);
+        debugger.breakpoints.push(Break::Cycle(1));
+        assert!(matches!(debugger.cont(), Stop::Break));
+        assert_eq!(debugger.cpu.cycle, 0);
+        assert!(matches!(debugger.cont(), Stop::Halted));
+        while debugger.back().is_none() {}
+        assert_eq!(
+            debugger.screen(),
+            ".".repeat(W) + "\n" + &(".".repeat(W) + "\n").repeat(H - 1)
+        );
+    }
+
+    #[test]
+    fn test_debugger_limit() {
+        let mut debugger = Debugger::new(parse("jmp 0").unwrap(), W, H);
+        assert!(matches!(debugger.cont(), Stop::Limit));
+        assert_eq!(debugger.history.len(), HISTORY);
+        while debugger.back().is_none() {}
+        assert!(matches!(debugger.back(), Some(Stop::Forgotten)));
+        assert_eq!(debugger.cpu.cycle, LIMIT - HISTORY);
+    }
+
+    #[test]
+    fn test_compile_letters() {
+        let image = typeset("RFZEKBFA", W, H).unwrap();
+        assert_eq!(Ok(image.clone()), bitmap(LETTERS));
+        let program = compile(&image).unwrap();
+        assert_eq!(draw(&program, W, H), Ok(LETTERS.to_string()));
+        let source = disassemble(&program);
+        assert_eq!(parse(&source), Ok(program));
+    }
+
+    #[test]
+    fn test_compile_sizes() {
+        let image = bitmap("###..\n#...#\n.....").unwrap();
+        let program = compile(&image).unwrap();
+        assert_eq!(
+            draw(&program, 5, 3),
+            Ok("###..\n#...#\n.....\n".to_string())
+        );
+        assert_eq!(
+            compile(&bitmap(".#").unwrap()),
+            Err(Error::Undrawable { x: 0, y: 0 }),
+        );
+        assert_eq!(
+            compile(&bitmap("#.\n..").unwrap()),
+            Err(Error::Undrawable { x: 1, y: 0 }),
+        );
+        assert_eq!(bitmap("##\n#"), Err(Error::Ragged { row: 2 }));
+        assert_eq!(
+            compile(&[vec![true, true], vec![true]]),
+            Err(Error::Ragged { row: 2 })
+        );
+        let program = parse(EXAMPLE).unwrap();
+        assert_eq!(draw(&program, 0, 6), Ok("\n".repeat(6)));
+        assert_eq!(draw(&program, 40, 0), Ok(String::new()));
+        let mut debugger = Debugger::new(program, 10, 2);
+        debugger.cont();
+        assert_eq!(debugger.screen(), "##..##..##\n....##....\n");
+        assert!(debugger.status().starts_with("cycle 240"));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse("noop\nhalt"),
+            Err(Error::UnknownOpcode {
+                line: 2,
+                opcode: "halt".to_string(),
+            }),
+        );
+        assert_eq!(
+            parse("addx"),
+            Err(Error::WrongOperands {
+                line: 1,
+                expected: 1,
+            }),
+        );
+        assert_eq!(puzzle2("jmp 0"), Err(Error::TooManyCycles { limit: LIMIT }),);
+        assert_eq!(
+            puzzle2("set a 100000\nmul a a"),
+            Err(Error::Overflow { line: 2 })
+        );
+        assert_eq!(
+            puzzle1(&format!("addx 2000000000{}", "\nnoop".repeat(20))),
+            Err(Error::SignalOverflow { cycle: 20 })
+        );
+        let mut debugger = Debugger::new(parse("addx 2147483647\nnoop").unwrap(), W, H);
+        assert!(matches!(
+            debugger.cont(),
+            Stop::Fault(Error::Overflow { line: 1 })
+        ));
+        assert_eq!(debugger.cpu.cycle, 1);
     }
 }
@@ -1,112 +1,635 @@
-use std::mem;
+use std::{collections::HashMap, fmt, hash::Hash, iter::Peekable, mem, ops::Range, str::Chars};
 
-use regex::Regex;
+use num_bigint::BigUint;
+use num_traits::{CheckedSub, ToPrimitive, Zero};
 
-enum Operator {
-    Plus,
-    Times,
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    BadHeader { line: usize },
+    BadField { line: usize, field: String },
+    MissingField { monkey: usize, field: &'static str },
+    BadExpr { line: usize, expr: String },
+    NoMonkey { monkey: usize, target: usize },
+    Overflow { a: usize, op: BinOp, b: usize },
+    Undefined { a: usize, op: BinOp, b: usize },
+    NotModular { op: BinOp, modulus: usize },
+    ZeroDivide,
 }
 
-use Operator::*;
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadHeader { line } => write!(f, "line {line}: expected `Monkey <n>:`"),
+            Error::BadField { line, field } => write!(f, "line {line}: bad {field:?}"),
+            Error::MissingField { monkey, field } => {
+                write!(f, "monkey {monkey}: missing {field:?}")
+            }
+            Error::BadExpr { line, expr } => write!(f, "line {line}: bad expression {expr:?}"),
+            Error::NoMonkey { monkey, target } => {
+                write!(f, "monkey {monkey}: throws to missing monkey {target}")
+            }
+            Error::Overflow { a, op, b } => write!(f, "{a} {op} {b} overflows"),
+            Error::Undefined { a, op, b } => write!(f, "{a} {op} {b} is undefined"),
+            Error::NotModular { op, modulus } => {
+                write!(f, "can't use {op} on worry levels modulo {modulus}")
+            }
+            Error::ZeroDivide => write!(f, "can't divide worry levels by zero"),
+        }
+    }
+}
 
-struct Operation {
-    left: Option<usize>,
-    operator: Operator,
-    right: Option<usize>,
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Mod,
 }
 
-impl Operation {
-    fn eval(&self, x: usize) -> usize {
-        let left = self.left.unwrap_or(x);
-        let right = self.right.unwrap_or(x);
-        match self.operator {
-            Plus => left + right,
-            Times => left * right,
+impl fmt::Display for BinOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            BinOp::Add => "+",
+            BinOp::Sub => "-",
+            BinOp::Mul => "*",
+            BinOp::Div => "/",
+            BinOp::Pow => "^",
+            BinOp::Mod => "%",
+        };
+        write!(f, "{symbol}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Old,
+    Num(usize),
+    Bin(Box<Expr>, BinOp, Box<Expr>),
+}
+
+impl Expr {
+    pub fn eval<S: Strategy>(&self, strategy: &S, old: &S::Worry) -> Result<S::Worry, Error> {
+        match self {
+            Expr::Old => Ok(old.clone()),
+            Expr::Num(n) => Ok(strategy.worry(*n)),
+            Expr::Bin(a, op, b) => {
+                strategy.apply(*op, &a.eval(strategy, old)?, &b.eval(strategy, old)?)
+            }
+        }
+    }
+}
+
+// recursive descent, with `^` binding tightest and to the right, then `*`, `/` and `%`, then `+`
+// and `-`
+struct ExprParser<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl ExprParser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+        self.chars.peek().copied()
+    }
+
+    fn sum(&mut self) -> Option<Expr> {
+        let mut left = self.product()?;
+        while let Some(op) = match self.peek() {
+            Some('+') => Some(BinOp::Add),
+            Some('-') => Some(BinOp::Sub),
+            _ => None,
+        } {
+            self.chars.next();
+            left = Expr::Bin(Box::new(left), op, Box::new(self.product()?));
+        }
+        Some(left)
+    }
+
+    fn product(&mut self) -> Option<Expr> {
+        let mut left = self.power()?;
+        while let Some(op) = match self.peek() {
+            Some('*') => Some(BinOp::Mul),
+            Some('/') => Some(BinOp::Div),
+            Some('%') => Some(BinOp::Mod),
+            _ => None,
+        } {
+            self.chars.next();
+            left = Expr::Bin(Box::new(left), op, Box::new(self.power()?));
+        }
+        Some(left)
+    }
+
+    fn power(&mut self) -> Option<Expr> {
+        let base = self.atom()?;
+        if self.peek() == Some('^') {
+            self.chars.next();
+            return Some(Expr::Bin(
+                Box::new(base),
+                BinOp::Pow,
+                Box::new(self.power()?),
+            ));
         }
+        Some(base)
     }
+
+    fn atom(&mut self) -> Option<Expr> {
+        match self.peek()? {
+            '(' => {
+                self.chars.next();
+                let e = self.sum()?;
+                (self.peek() == Some(')')).then(|| self.chars.next())?;
+                Some(e)
+            }
+            c if c.is_ascii_digit() => {
+                let mut n = String::new();
+                while let Some(d) = self.chars.next_if(|c| c.is_ascii_digit()) {
+                    n.push(d);
+                }
+                n.parse().ok().map(Expr::Num)
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphabetic()) {
+                    word.push(c);
+                }
+                (word == "old").then_some(Expr::Old)
+            }
+        }
+    }
+}
+
+pub fn parse_expr(s: &str) -> Option<Expr> {
+    let mut parser = ExprParser {
+        chars: s.chars().peekable(),
+    };
+    let e = parser.sum()?;
+    parser.peek().is_none().then_some(e)
 }
 
-struct Monkey {
+pub struct Monkey {
     items: Vec<usize>,
-    operation: Operation,
+    operation: Expr,
     test: usize,
     if_true: usize,
     if_false: usize,
 }
 
-fn parse_operand(s: &str) -> Option<usize> {
-    match s {
-        "old" => None,
-        _ => Some(s.parse().unwrap()),
-    }
-}
-
-fn parse(input: &str) -> Vec<Monkey> {
-    let lines = input.lines().collect::<Vec<_>>();
-    let n = lines.len() + 1;
-    assert_eq!(n % 7, 0);
-
-    let re_items = Regex::new(r"^  Starting items: (.+)$").unwrap();
-    let re_operation = Regex::new(r"^  Operation: new = (.+) (.) (.+)$").unwrap();
-    let re_test = Regex::new(r"^  Test: divisible by (.+)$").unwrap();
-    let re_if = Regex::new(r"^    If .+: throw to monkey (.+)$").unwrap();
-
-    (0..n / 7)
-        .map(|i| {
-            let j = i * 7;
-            Monkey {
-                items: re_items.captures(lines[j + 1]).unwrap()[1]
-                    .split(", ")
-                    .map(|s| s.parse().unwrap())
-                    .collect(),
-                operation: {
-                    let cap = re_operation.captures(lines[j + 2]).unwrap();
-                    Operation {
-                        left: parse_operand(&cap[1]),
-                        operator: match &cap[2] {
-                            "+" => Operator::Plus,
-                            "*" => Operator::Times,
-                            _ => panic!("unknown operator"),
-                        },
-                        right: parse_operand(&cap[3]),
+// blocks are separated by blank lines and fields are found by name, so the order of the fields
+// doesn't matter and unknown ones are ignored
+pub fn parse(input: &str) -> Result<Vec<Monkey>, Error> {
+    let mut monkeys = vec![];
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()));
+    while let Some((n, header)) = lines.find(|(_, line)| !line.is_empty()) {
+        let id = header
+            .strip_prefix("Monkey ")
+            .and_then(|s| s.strip_suffix(':'))
+            .and_then(|s| s.parse::<usize>().ok())
+            .filter(|&id| id == monkeys.len())
+            .ok_or(Error::BadHeader { line: n })?;
+        let (mut items, mut operation, mut test, mut if_true, mut if_false) =
+            (None, None, None, None, None);
+        for (n, line) in lines.by_ref().take_while(|(_, line)| !line.is_empty()) {
+            let (field, value) = line.split_once(':').ok_or(Error::BadField {
+                line: n,
+                field: line.to_string(),
+            })?;
+            let bad = || Error::BadField {
+                line: n,
+                field: field.to_string(),
+            };
+            let value = value.trim();
+            let number = |prefix: &str| -> Result<usize, Error> {
+                value
+                    .strip_prefix(prefix)
+                    .and_then(|s| s.trim().parse().ok())
+                    .ok_or_else(bad)
+            };
+            match field {
+                "Starting items" => {
+                    items = Some(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|s| !s.is_empty())
+                            .map(|s| s.parse().map_err(|_| bad()))
+                            .collect::<Result<Vec<_>, _>>()?,
+                    )
+                }
+                "Operation" => {
+                    let expr = value.strip_prefix("new =").ok_or_else(bad)?;
+                    operation = Some(parse_expr(expr).ok_or(Error::BadExpr {
+                        line: n,
+                        expr: expr.trim().to_string(),
+                    })?);
+                }
+                "Test" => {
+                    let divisor = number("divisible by")?;
+                    test = Some(Some(divisor).filter(|&t| t > 0).ok_or_else(bad)?);
+                }
+                "If true" => if_true = Some(number("throw to monkey")?),
+                "If false" => if_false = Some(number("throw to monkey")?),
+                _ => {}
+            }
+        }
+        let missing = |field| Error::MissingField { monkey: id, field };
+        monkeys.push(Monkey {
+            items: items.ok_or_else(|| missing("Starting items"))?,
+            operation: operation.ok_or_else(|| missing("Operation"))?,
+            test: test.ok_or_else(|| missing("Test"))?,
+            if_true: if_true.ok_or_else(|| missing("If true"))?,
+            if_false: if_false.ok_or_else(|| missing("If false"))?,
+        });
+    }
+    for (monkey, m) in monkeys.iter().enumerate() {
+        for target in [m.if_true, m.if_false] {
+            if target >= monkeys.len() {
+                return Err(Error::NoMonkey { monkey, target });
+            }
+        }
+    }
+    Ok(monkeys)
+}
+
+// how worry levels are represented, how the operations are computed on them and how they're
+// relieved after each inspection
+pub trait Strategy {
+    type Worry: Clone + Eq + Hash + fmt::Display;
+
+    fn worry(&self, n: usize) -> Self::Worry;
+    fn apply(&self, op: BinOp, a: &Self::Worry, b: &Self::Worry) -> Result<Self::Worry, Error>;
+    fn relieve(&self, w: Self::Worry) -> Self::Worry;
+    fn divisible(&self, w: &Self::Worry, n: usize) -> bool;
+}
+
+fn checked(op: BinOp, a: usize, b: usize) -> Result<usize, Error> {
+    let result = match op {
+        BinOp::Add => a.checked_add(b),
+        BinOp::Mul => a.checked_mul(b),
+        BinOp::Pow => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+        BinOp::Sub => return a.checked_sub(b).ok_or(Error::Undefined { a, op, b }),
+        BinOp::Div => return a.checked_div(b).ok_or(Error::Undefined { a, op, b }),
+        BinOp::Mod => return a.checked_rem(b).ok_or(Error::Undefined { a, op, b }),
+    };
+    result.ok_or(Error::Overflow { a, op, b })
+}
+
+// machine words that fail instead of overflowing, divided by `divide` after each inspection,
+// which is 3 for the first puzzle and 1 to leave the worry alone
+pub struct Checked {
+    divide: usize,
+}
+
+impl Checked {
+    pub fn new(divide: usize) -> Result<Self, Error> {
+        match divide {
+            0 => Err(Error::ZeroDivide),
+            divide => Ok(Checked { divide }),
+        }
+    }
+}
+
+impl Strategy for Checked {
+    type Worry = usize;
+
+    fn worry(&self, n: usize) -> usize {
+        n
+    }
+
+    fn apply(&self, op: BinOp, &a: &usize, &b: &usize) -> Result<usize, Error> {
+        checked(op, a, b)
+    }
+
+    fn relieve(&self, w: usize) -> usize {
+        w / self.divide
+    }
+
+    fn divisible(&self, &w: &usize, n: usize) -> bool {
+        w.is_multiple_of(n)
+    }
+}
+
+fn gcd(a: usize, b: usize) -> usize {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Residue {
+    pub value: usize,
+    // whether `value` is the real worry level and not just its remainder
+    pub exact: bool,
+}
+
+impl fmt::Display for Residue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.exact {
+            write!(f, "{}", self.value)
+        } else {
+            write!(f, "{}…", self.value)
+        }
+    }
+}
+
+// worry levels modulo the lowest common multiple of the tests, which keeps every test's outcome;
+// subtraction assumes worry never really goes negative, and division, modulo by anything not
+// dividing the modulus, and exponents only work while the values involved are still exact
+pub struct Modular {
+    pub modulus: usize,
+}
+
+impl Modular {
+    pub fn lcm(monkeys: &[Monkey]) -> Result<Self, Error> {
+        let modulus = monkeys.iter().try_fold(1, |a, monkey| {
+            let b = monkey.test / gcd(a, monkey.test);
+            a.checked_mul(b).ok_or(Error::Overflow {
+                a,
+                op: BinOp::Mul,
+                b,
+            })
+        })?;
+        Ok(Modular { modulus })
+    }
+}
+
+impl Strategy for Modular {
+    type Worry = Residue;
+
+    fn worry(&self, n: usize) -> Residue {
+        Residue {
+            value: n % self.modulus,
+            exact: n < self.modulus,
+        }
+    }
+
+    fn apply(&self, op: BinOp, a: &Residue, b: &Residue) -> Result<Residue, Error> {
+        if a.exact && b.exact {
+            match checked(op, a.value, b.value) {
+                Err(Error::Overflow { .. }) => {}
+                result => return result.map(|n| self.worry(n)),
+            }
+        }
+        let m = self.modulus as u128;
+        let (x, y) = (a.value as u128, b.value as u128);
+        let value = match op {
+            BinOp::Add => (x + y) % m,
+            BinOp::Sub => (x + m - y) % m,
+            BinOp::Mul => x * y % m,
+            BinOp::Pow if b.exact => {
+                let (mut base, mut exp, mut result) = (x, y, 1 % m);
+                while exp > 0 {
+                    if exp & 1 == 1 {
+                        result = result * base % m;
                     }
-                },
-                test: re_test.captures(lines[j + 3]).unwrap()[1].parse().unwrap(),
-                if_true: re_if.captures(lines[j + 4]).unwrap()[1].parse().unwrap(),
-                if_false: re_if.captures(lines[j + 5]).unwrap()[1].parse().unwrap(),
+                    base = base * base % m;
+                    exp >>= 1;
+                }
+                result
             }
+            BinOp::Mod if b.exact && y > 0 && m.is_multiple_of(y) => x % y,
+            _ => {
+                return Err(Error::NotModular {
+                    op,
+                    modulus: self.modulus,
+                })
+            }
+        };
+        Ok(Residue {
+            value: value as usize,
+            exact: false,
         })
-        .collect()
+    }
+
+    fn relieve(&self, w: Residue) -> Residue {
+        w
+    }
+
+    fn divisible(&self, w: &Residue, n: usize) -> bool {
+        w.value.is_multiple_of(n)
+    }
 }
 
-fn solve(mut monkeys: Vec<Monkey>, rounds: usize, f: impl Fn(usize) -> usize) -> isize {
-    let mut inspections = vec![0isize; monkeys.len()];
-    for _ in 0..rounds {
-        for i in 0..monkeys.len() {
-            for item in mem::take(&mut monkeys[i].items) {
+// exact worry levels however large they get, which is only practical for a few rounds
+pub struct Big {
+    divide: usize,
+}
+
+impl Big {
+    pub fn new(divide: usize) -> Result<Self, Error> {
+        match divide {
+            0 => Err(Error::ZeroDivide),
+            divide => Ok(Big { divide }),
+        }
+    }
+}
+
+impl Strategy for Big {
+    type Worry = BigUint;
+
+    fn worry(&self, n: usize) -> BigUint {
+        BigUint::from(n)
+    }
+
+    fn apply(&self, op: BinOp, a: &BigUint, b: &BigUint) -> Result<BigUint, Error> {
+        let undefined = || Error::Undefined {
+            a: a.to_usize().unwrap_or(usize::MAX),
+            op,
+            b: b.to_usize().unwrap_or(usize::MAX),
+        };
+        match op {
+            BinOp::Add => Ok(a + b),
+            BinOp::Sub => a.checked_sub(b).ok_or_else(undefined),
+            BinOp::Mul => Ok(a * b),
+            BinOp::Div if !b.is_zero() => Ok(a / b),
+            BinOp::Mod if !b.is_zero() => Ok(a % b),
+            BinOp::Div | BinOp::Mod => Err(undefined()),
+            BinOp::Pow => match b.to_u32() {
+                Some(exp) => Ok(a.pow(exp)),
+                None => Err(Error::Overflow {
+                    a: a.to_usize().unwrap_or(usize::MAX),
+                    op,
+                    b: usize::MAX,
+                }),
+            },
+        }
+    }
+
+    fn relieve(&self, w: BigUint) -> BigUint {
+        w / self.divide
+    }
+
+    fn divisible(&self, w: &BigUint, n: usize) -> bool {
+        (w % n).is_zero()
+    }
+}
+
+// a monkey's items as item numbers, counting from the first monkey's first item, and worry levels
+pub type Holdings<W> = Vec<Vec<(usize, W)>>;
+
+pub struct Simulation<'a, S: Strategy> {
+    monkeys: &'a [Monkey],
+    strategy: &'a S,
+    items: Holdings<S::Worry>,
+    // inspections by each monkey in each round
+    history: Vec<Vec<usize>>,
+    // the monkeys that have held each item, starting with the first
+    journeys: Vec<Vec<usize>>,
+    // after each round, starting with the initial state
+    holdings: Vec<Holdings<S::Worry>>,
+    // item numbers don't matter to what happens next, so states are only worry levels
+    seen: HashMap<Vec<Vec<S::Worry>>, usize>,
+    cycle: Option<(usize, usize)>,
+}
+
+impl<'a, S: Strategy> Simulation<'a, S> {
+    pub fn new(monkeys: &'a [Monkey], strategy: &'a S) -> Self {
+        let mut items = vec![];
+        let mut journeys = vec![];
+        for (i, monkey) in monkeys.iter().enumerate() {
+            let mut held = vec![];
+            for &n in &monkey.items {
+                held.push((journeys.len(), strategy.worry(n)));
+                journeys.push(vec![i]);
+            }
+            items.push(held);
+        }
+        let mut simulation = Simulation {
+            monkeys,
+            strategy,
+            items: items.clone(),
+            history: vec![],
+            journeys,
+            holdings: vec![items],
+            seen: HashMap::new(),
+            cycle: None,
+        };
+        simulation.seen.insert(simulation.state(), 0);
+        simulation
+    }
+
+    fn state(&self) -> Vec<Vec<S::Worry>> {
+        self.items
+            .iter()
+            .map(|held| held.iter().map(|(_, w)| w.clone()).collect())
+            .collect()
+    }
+
+    pub fn history(&self) -> &[Vec<usize>] {
+        &self.history
+    }
+
+    pub fn journey(&self, item: usize) -> &[usize] {
+        &self.journeys[item]
+    }
+
+    pub fn holdings(&self, round: usize) -> &Holdings<S::Worry> {
+        &self.holdings[round]
+    }
+
+    // the first round that repeats and how many rounds it takes to come back to it
+    pub fn cycle(&self) -> Option<(usize, usize)> {
+        self.cycle
+    }
+
+    pub fn round(&mut self) -> Result<(), Error> {
+        let mut inspections = vec![0; self.monkeys.len()];
+        for (i, monkey) in self.monkeys.iter().enumerate() {
+            for (item, old) in mem::take(&mut self.items[i]) {
                 inspections[i] += 1;
-                let worry = f(monkeys[i].operation.eval(item));
-                let j = if worry % monkeys[i].test == 0 {
-                    monkeys[i].if_true
+                let strategy = self.strategy;
+                let worry = strategy.relieve(monkey.operation.eval(strategy, &old)?);
+                let j = if strategy.divisible(&worry, monkey.test) {
+                    monkey.if_true
                 } else {
-                    monkeys[i].if_false
+                    monkey.if_false
                 };
-                monkeys[j].items.push(worry);
+                self.journeys[item].push(j);
+                self.items[j].push((item, worry));
+            }
+        }
+        self.history.push(inspections);
+        self.holdings.push(self.items.clone());
+        let round = self.history.len();
+        if self.cycle.is_none() {
+            if let Some(start) = self.seen.insert(self.state(), round) {
+                self.cycle = Some((start, round - start));
             }
         }
+        Ok(())
     }
-    inspections.sort_by_key(|x| -x);
-    inspections[0] * inspections[1]
+
+    // the total inspections by each monkey after that many rounds, only simulating until the
+    // rounds start repeating
+    pub fn totals(&mut self, rounds: usize) -> Result<Vec<usize>, Error> {
+        while self.history.len() < rounds && self.cycle.is_none() {
+            self.round()?;
+        }
+        let sum = |range: Range<usize>| {
+            self.history[range]
+                .iter()
+                .fold(vec![0; self.monkeys.len()], |mut sum, counts| {
+                    sum.iter_mut().zip(counts).for_each(|(a, b)| *a += b);
+                    sum
+                })
+        };
+        let (start, period) = match self.cycle {
+            Some(cycle) if rounds > self.history.len() => cycle,
+            _ => return Ok(sum(0..rounds)),
+        };
+        let laps = (rounds - start) / period;
+        let head = sum(0..start);
+        let lap = sum(start..start + period);
+        let tail = sum(start..start + (rounds - start) % period);
+        (0..self.monkeys.len())
+            .map(|i| {
+                lap[i]
+                    .checked_mul(laps)
+                    .and_then(|n| n.checked_add(head[i] + tail[i]))
+                    .ok_or(Error::Overflow {
+                        a: lap[i],
+                        op: BinOp::Mul,
+                        b: laps,
+                    })
+            })
+            .collect()
+    }
+}
+
+// how many items each monkey inspected
+pub fn inspections<S: Strategy>(
+    monkeys: &[Monkey],
+    rounds: usize,
+    strategy: &S,
+) -> Result<Vec<usize>, Error> {
+    Simulation::new(monkeys, strategy).totals(rounds)
 }
 
-pub fn puzzle1(input: &str) -> isize {
-    solve(parse(input), 20, |x| x / 3)
+// the product of the two largest inspection counts
+pub fn business(inspections: &[usize]) -> usize {
+    let mut sorted = inspections.to_vec();
+    sorted.sort_unstable_by(|a, b| b.cmp(a));
+    sorted.iter().take(2).product()
 }
 
-pub fn puzzle2(input: &str) -> isize {
-    let monkeys = parse(input);
-    let n: usize = monkeys.iter().map(|monkey| monkey.test).product();
-    solve(monkeys, 10000, |x| x % n)
+pub fn puzzle1(input: &str) -> Result<usize, Error> {
+    let monkeys = parse(input)?;
+    Ok(business(&inspections(
+        &monkeys,
+        20,
+        &Checked::new(3).unwrap(),
+    )?))
+}
+
+pub fn puzzle2(input: &str) -> Result<usize, Error> {
+    let monkeys = parse(input)?;
+    let strategy = Modular::lcm(&monkeys)?;
+    Ok(business(&inspections(&monkeys, 10000, &strategy)?))
 }
 
 #[cfg(test)]
@@ -118,21 +641,149 @@ mod tests {
 
     #[test]
     fn test_puzzle1_example() {
-        assert_eq!(puzzle1(EXAMPLE), 10605);
+        assert_eq!(puzzle1(EXAMPLE), Ok(10605));
     }
 
     #[test]
     fn test_puzzle1_input() {
-        assert_eq!(puzzle1(INPUT), 50616);
+        assert_eq!(puzzle1(INPUT), Ok(50616));
     }
 
     #[test]
     fn test_puzzle2_example() {
-        assert_eq!(puzzle2(EXAMPLE), 2713310158);
+        assert_eq!(puzzle2(EXAMPLE), Ok(2713310158));
     }
 
     #[test]
     fn test_puzzle2_input() {
-        assert_eq!(puzzle2(INPUT), 11309046332);
+        assert_eq!(puzzle2(INPUT), Ok(11309046332));
+    }
+
+    #[test]
+    fn test_parse_expr() {
+        let e = parse_expr("(old + 3) * old ^ 2 ^ 1 - 10 / 3 % 2").unwrap();
+        let c = Checked::new(1).unwrap();
+        assert_eq!(e.eval(&c, &2), Ok(19));
+        assert_eq!(parse_expr(" old*old ").unwrap().eval(&c, &7), Ok(49));
+        assert_eq!(parse_expr("2 ^ 3 ^ 2").unwrap().eval(&c, &0), Ok(512));
+        assert_eq!(parse_expr("old +"), None);
+        assert_eq!(parse_expr("(old"), None);
+        assert_eq!(parse_expr("new"), None);
+    }
+
+    #[test]
+    fn test_parse_fields() {
+        let shuffled = "\
+Monkey 0:
+  Test: divisible by 2
+  Operation: new = (old - 1) * 3
+  Note: ignored
+  If false: throw to monkey 1
+  If true: throw to monkey 1
+  Starting items: 5
+
+Monkey 1:
+  Starting items:
+  Operation: new = old
+  Test: divisible by 5
+  If true: throw to monkey 0
+  If false: throw to monkey 0
+";
+        let monkeys = parse(shuffled).unwrap();
+        assert_eq!(monkeys[0].items, vec![5]);
+        assert_eq!(
+            monkeys[0].operation.eval(&Checked::new(1).unwrap(), &5),
+            Ok(12)
+        );
+        assert!(monkeys[1].items.is_empty());
+        assert_eq!(
+            parse(&shuffled.replace("  Test: divisible by 5\n", "")).err(),
+            Some(Error::MissingField {
+                monkey: 1,
+                field: "Test"
+            }),
+        );
+        assert_eq!(
+            parse(&shuffled.replace("monkey 0", "monkey 2")).err(),
+            Some(Error::NoMonkey {
+                monkey: 1,
+                target: 2
+            }),
+        );
+        assert_eq!(
+            parse(&shuffled.replace("(old - 1)", "(old - )")).err(),
+            Some(Error::BadExpr {
+                line: 3,
+                expr: "(old - ) * 3".to_string()
+            }),
+        );
+        assert_eq!(
+            parse(&shuffled.replace("divisible by 5", "divisible by 0")).err(),
+            Some(Error::BadField {
+                line: 12,
+                field: "Test".to_string()
+            }),
+        );
+    }
+
+    #[test]
+    fn test_strategies() {
+        let monkeys = parse(EXAMPLE).unwrap();
+        let modular = Modular::lcm(&monkeys).unwrap();
+        assert_eq!(modular.modulus, 96577);
+        assert_eq!(
+            inspections(&monkeys, 20, &Big::new(3).unwrap()),
+            inspections(&monkeys, 20, &Checked::new(3).unwrap()),
+        );
+        assert_eq!(
+            inspections(&monkeys, 10, &Big::new(1).unwrap()),
+            inspections(&monkeys, 10, &modular),
+        );
+        assert!(matches!(
+            inspections(&monkeys, 10000, &Checked::new(1).unwrap()),
+            Err(Error::Overflow { .. }),
+        ));
+        let halving = parse(&EXAMPLE.replace("old * 19", "old / 2")).unwrap();
+        assert_eq!(
+            inspections(&halving, 20, &Modular::lcm(&halving).unwrap()),
+            Err(Error::NotModular {
+                op: BinOp::Div,
+                modulus: 96577
+            }),
+        );
+        assert!(matches!(Checked::new(0), Err(Error::ZeroDivide)));
+        assert!(matches!(Big::new(0), Err(Error::ZeroDivide)));
+    }
+
+    #[test]
+    fn test_simulation_example() {
+        let monkeys = parse(EXAMPLE).unwrap();
+        let strategy = Checked::new(3).unwrap();
+        let mut simulation = Simulation::new(&monkeys, &strategy);
+        simulation.round().unwrap();
+        assert_eq!(simulation.history(), [vec![2, 4, 3, 5]]);
+        assert_eq!(
+            simulation.holdings(1)[0],
+            vec![(2, 20), (3, 23), (4, 27), (5, 26)]
+        );
+        assert_eq!(simulation.journey(0), [0, 3, 1]);
+        assert_eq!(simulation.totals(20), Ok(vec![101, 95, 7, 105]));
+        assert_eq!(simulation.history().len(), 20);
+    }
+
+    #[test]
+    fn test_cycle_input() {
+        let monkeys = parse(INPUT).unwrap();
+        let strategy = Modular::lcm(&monkeys).unwrap();
+        let mut simulation = Simulation::new(&monkeys, &strategy);
+        assert_eq!(business(&simulation.totals(10000).unwrap()), 11309046332);
+        assert_eq!(simulation.cycle(), Some((113, 44)));
+        assert_eq!(simulation.history().len(), 157);
+        let mut rounds = Simulation::new(&monkeys, &strategy);
+        for _ in 0..1000 {
+            rounds.round().unwrap();
+        }
+        assert_eq!(rounds.totals(1000), simulation.totals(1000));
+        assert!(simulation.totals(1_000_000_000_000).is_ok());
     }
 }
@@ -1,16 +1,16 @@
-use std::collections::BinaryHeap;
+use std::{cmp::Ordering, collections::BinaryHeap};
 
-struct Heightmap {
+pub struct Heightmap {
     grid: Vec<Vec<u8>>,
     start: (usize, usize),
     end: (usize, usize),
 }
 
-fn parse_char(c: char) -> u8 {
-    c as u8 - 'a' as u8
+pub fn parse_char(c: char) -> u8 {
+    c as u8 - b'a'
 }
 
-fn parse(input: &str) -> Heightmap {
+pub fn parse(input: &str) -> Heightmap {
     let mut start = None;
     let mut end = None;
     Heightmap {
@@ -39,54 +39,231 @@ fn parse(input: &str) -> Heightmap {
     }
 }
 
-fn solve(
-    grid: Vec<Vec<u8>>,
-    mut queue: BinaryHeap<(isize, (usize, usize))>,
-    end: (usize, usize),
-) -> isize {
+impl Heightmap {
+    pub fn start(&self) -> (usize, usize) {
+        self.start
+    }
+}
+
+// which steps are allowed and what they cost; the puzzle's rules are `Rules::default()`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rules {
+    pub up: u8,
+    pub down: u8,
+    pub diagonal: bool,
+    // the cost of stepping onto a square of each height
+    pub costs: [usize; 26],
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            up: 1,
+            down: 25,
+            diagonal: false,
+            costs: [1; 26],
+        }
+    }
+}
+
+impl Rules {
+    fn allows(&self, from: u8, to: u8) -> bool {
+        to <= from.saturating_add(self.up) && from <= to.saturating_add(self.down)
+    }
+
+    // the squares next to `(y, x)`, whether or not the step is allowed
+    fn adjacent(
+        &self,
+        grid: &[Vec<u8>],
+        (y, x): (usize, usize),
+    ) -> impl Iterator<Item = (usize, usize)> {
+        let h = grid.len();
+        let w = grid[0].len();
+        let diagonal = [(-1, -1), (-1, 1), (1, -1), (1, 1)];
+        let n = if self.diagonal { 4 } else { 0 };
+        [(-1, 0), (1, 0), (0, -1), (0, 1)]
+            .into_iter()
+            .chain(diagonal.into_iter().take(n))
+            .filter_map(move |(dy, dx)| {
+                let ny = y.checked_add_signed(dy).filter(|&ny| ny < h)?;
+                let nx = x.checked_add_signed(dx).filter(|&nx| nx < w)?;
+                Some((ny, nx))
+            })
+    }
+}
+
+// every square from the starting one to `E`, so the number of steps is one less
+pub struct Route {
+    pub path: Vec<(usize, usize)>,
+    pub cost: usize,
+}
+
+impl Route {
+    pub fn steps(&self) -> usize {
+        self.path.len() - 1
+    }
+
+    pub fn start(&self) -> (usize, usize) {
+        self.path[0]
+    }
+}
+
+// the cheapest cost of reaching each square from any of the starts, and the square it was reached
+// from; in reverse, steps are taken backwards so the costs are of getting from each square to the
+// starts
+type Search = Vec<Vec<Option<(usize, Option<(usize, usize)>)>>>;
+
+fn search(
+    grid: &[Vec<u8>],
+    rules: &Rules,
+    starts: &[(usize, usize)],
+    reverse: bool,
+    stop: Option<(usize, usize)>,
+) -> Search {
     let h = grid.len();
     let w = grid[0].len();
     let mut visited = vec![vec![None; w]; h];
-    while let Some((d, (y, x))) = queue.pop() {
-        if let Some(_) = visited[y][x] {
+    let mut queue = starts
+        .iter()
+        .map(|&start| (0isize, start, None))
+        .collect::<BinaryHeap<_>>();
+    while let Some((d, (y, x), from)) = queue.pop() {
+        if visited[y][x].is_some() {
             continue;
         }
-        visited[y][x] = Some(d);
-        if 0 < y && grid[y - 1][x] <= grid[y][x] + 1 {
-            queue.push((d - 1, (y - 1, x)));
-        }
-        if y + 1 < h && grid[y + 1][x] <= grid[y][x] + 1 {
-            queue.push((d - 1, (y + 1, x)));
+        visited[y][x] = Some((-d as usize, from));
+        if Some((y, x)) == stop {
+            break;
         }
-        if 0 < x && grid[y][x - 1] <= grid[y][x] + 1 {
-            queue.push((d - 1, (y, x - 1)));
-        }
-        if x + 1 < w && grid[y][x + 1] <= grid[y][x] + 1 {
-            queue.push((d - 1, (y, x + 1)));
+        for (ny, nx) in rules.adjacent(grid, (y, x)) {
+            let (here, there) = (grid[y][x], grid[ny][nx]);
+            let (allowed, cost) = if reverse {
+                (rules.allows(there, here), rules.costs[here as usize])
+            } else {
+                (rules.allows(here, there), rules.costs[there as usize])
+            };
+            if allowed && visited[ny][nx].is_none() {
+                queue.push((d - cost as isize, (ny, nx), Some((y, x))));
+            }
         }
     }
-    let (y, x) = end;
-    -visited[y][x].unwrap()
+    visited
 }
 
-pub fn puzzle1(input: &str) -> isize {
-    let Heightmap { grid, start, end } = parse(input);
-    let mut queue = BinaryHeap::new();
-    queue.push((0, start));
-    solve(grid, queue, end)
+// following the squares each one was reached from back to a start
+fn trace(visited: &Search, (y, x): (usize, usize)) -> Option<Route> {
+    let (cost, mut from) = visited[y][x]?;
+    let mut path = vec![(y, x)];
+    while let Some((y, x)) = from {
+        path.push((y, x));
+        from = visited[y][x].unwrap().1;
+    }
+    Some(Route { path, cost })
 }
 
-pub fn puzzle2(input: &str) -> isize {
-    let Heightmap { grid, end, .. } = parse(input);
-    let mut queue = BinaryHeap::new();
-    for y in 0..grid.len() {
-        for x in 0..grid[y].len() {
-            if grid[y][x] == 0 {
-                queue.push((0, (y, x)));
+// the lowest squares, which are the possible starts for the second puzzle
+pub fn lowest(map: &Heightmap) -> Vec<(usize, usize)> {
+    let mut squares = vec![];
+    for (y, row) in map.grid.iter().enumerate() {
+        for (x, &height) in row.iter().enumerate() {
+            if height == 0 {
+                squares.push((y, x));
             }
         }
     }
-    solve(grid, queue, end)
+    squares
+}
+
+// the cheapest route from any of the starts
+pub fn route(map: &Heightmap, rules: &Rules, starts: &[(usize, usize)]) -> Option<Route> {
+    let visited = search(&map.grid, rules, starts, false, Some(map.end));
+    let mut route = trace(&visited, map.end)?;
+    route.path.reverse();
+    Some(route)
+}
+
+// the cost of getting from every square to `E`, from a single search backwards from it
+pub struct Field {
+    visited: Search,
+}
+
+impl Field {
+    pub fn new(map: &Heightmap, rules: &Rules) -> Self {
+        Field {
+            visited: search(&map.grid, rules, &[map.end], true, None),
+        }
+    }
+
+    pub fn cost(&self, (y, x): (usize, usize)) -> Option<usize> {
+        self.visited[y][x].map(|(cost, _)| cost)
+    }
+
+    pub fn route(&self, start: (usize, usize)) -> Option<Route> {
+        trace(&self.visited, start)
+    }
+
+    // the cheapest route from any of the starts
+    pub fn best(&self, starts: &[(usize, usize)]) -> Option<Route> {
+        let &start = starts
+            .iter()
+            .filter(|&&start| self.cost(start).is_some())
+            .min_by_key(|&&start| self.cost(start))?;
+        self.route(start)
+    }
+}
+
+// the route as arrows like in the puzzle, with diagonal steps as `/` and `\`, over either dots
+// or the heights themselves
+pub fn render(map: &Heightmap, route: &Route, heights: bool) -> String {
+    let mut cells = map
+        .grid
+        .iter()
+        .map(|row| {
+            row.iter()
+                .map(|&height| {
+                    if heights {
+                        (b'a' + height) as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if heights {
+        cells[map.start.0][map.start.1] = 'S';
+    }
+    for pair in route.path.windows(2) {
+        let ((y, x), (ny, nx)) = (pair[0], pair[1]);
+        cells[y][x] = match (ny.cmp(&y), nx.cmp(&x)) {
+            (Ordering::Equal, Ordering::Greater) => '>',
+            (Ordering::Equal, _) => '<',
+            (Ordering::Less, Ordering::Equal) => '^',
+            (_, Ordering::Equal) => 'v',
+            (Ordering::Less, Ordering::Greater) | (Ordering::Greater, Ordering::Less) => '/',
+            _ => '\\',
+        };
+    }
+    cells[map.end.0][map.end.1] = 'E';
+    cells
+        .into_iter()
+        .map(|row| row.into_iter().collect::<String>() + "\n")
+        .collect()
+}
+
+pub fn puzzle1(input: &str) -> usize {
+    let map = parse(input);
+    route(&map, &Rules::default(), &[map.start()])
+        .unwrap()
+        .steps()
+}
+
+pub fn puzzle2(input: &str) -> usize {
+    let map = parse(input);
+    Field::new(&map, &Rules::default())
+        .best(&lowest(&map))
+        .unwrap()
+        .steps()
 }
 
 #[cfg(test)]
@@ -115,4 +292,82 @@ mod tests {
     fn test_puzzle2_input() {
         assert_eq!(puzzle2(INPUT), 363);
     }
+
+    #[test]
+    fn test_route_example() {
+        let map = parse(EXAMPLE);
+        let climb = route(&map, &Rules::default(), &[map.start()]).unwrap();
+        assert_eq!(climb.steps(), 31);
+        assert_eq!(climb.start(), (0, 0));
+        assert_eq!(
+            render(&map, &climb, false),
+            "\
+v..v<<<<
+>v.vv<<^
+.v.v>E^^
+.>v>>>^^
+..>>>>>^
+",
+        );
+        let hike = route(&map, &Rules::default(), &lowest(&map)).unwrap();
+        assert_eq!(hike.steps(), 29);
+        assert_eq!(hike.start(), (4, 0));
+        assert_eq!(render(&map, &hike, true).lines().nth(4), Some(">^>>>>>^"));
+    }
+
+    #[test]
+    fn test_rules_example() {
+        let map = parse(EXAMPLE);
+        let climb = |rules: Rules| route(&map, &rules, &[map.start()]).unwrap();
+        let diagonal = Rules {
+            diagonal: true,
+            ..Rules::default()
+        };
+        assert_eq!(climb(diagonal).steps(), 27);
+        assert_eq!(
+            climb(Rules {
+                up: 2,
+                ..Rules::default()
+            })
+            .steps(),
+            27
+        );
+        let mut costs = [1; 26];
+        costs[2] = 10;
+        let expensive = climb(Rules {
+            costs,
+            ..Rules::default()
+        });
+        assert_eq!((expensive.steps(), expensive.cost), (33, 51));
+        let flat = Rules {
+            up: 0,
+            ..Rules::default()
+        };
+        assert!(route(&map, &flat, &[map.start()]).is_none());
+    }
+
+    #[test]
+    fn test_field_input() {
+        let map = parse(INPUT);
+        let mut costs = [1; 26];
+        costs[3] = 4;
+        let rules = Rules {
+            costs,
+            down: 3,
+            ..Rules::default()
+        };
+        let field = Field::new(&map, &rules);
+        let mut reachable = 0;
+        for start in lowest(&map).into_iter().step_by(50) {
+            let forward = route(&map, &rules, &[start]).map(|route| route.cost);
+            reachable += forward.is_some() as usize;
+            assert_eq!(field.cost(start), forward);
+            assert_eq!(field.route(start).map(|route| route.cost), forward);
+        }
+        assert!(reachable > 0);
+        let best = Field::new(&map, &Rules::default())
+            .best(&lowest(&map))
+            .unwrap();
+        assert_eq!((best.steps(), best.start()), (363, (13, 0)));
+    }
 }
@@ -1,108 +1,217 @@
-use std::cmp::Ordering;
+use std::{cmp::Ordering, fmt, slice, str::FromStr};
 
-#[derive(Clone, PartialEq)]
-enum Packet {
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    UnexpectedEnd,
+    Unexpected { at: usize, found: char },
+    TooLarge { at: usize },
+    Unpaired { line: usize },
+    Line { line: usize, error: Box<Error> },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnexpectedEnd => write!(f, "unexpected end of packet"),
+            Error::Unexpected { at, found } => write!(f, "unexpected {found:?} at column {at}"),
+            Error::TooLarge { at } => write!(f, "integer too large at column {at}"),
+            Error::Unpaired { line } => write!(f, "line {line}: packet has no pair"),
+            Error::Line { line, error } => write!(f, "line {line}: {error}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Packet {
     Integer(usize),
     List(Vec<Packet>),
 }
 
 use Packet::*;
 
-fn parse_partial_packet(mut s: &str) -> Option<(Packet, &str)> {
-    if s.is_empty() || s.starts_with(']') {
-        return None;
-    }
-    if s.starts_with('[') {
-        let mut v = vec![];
-        let mut s = &s[1..];
-        while let Some((packet, rest)) = parse_partial_packet(s) {
-            v.push(packet);
-            s = rest;
-            if s.starts_with(',') {
-                s = &s[1..];
-            } else {
-                break;
+// a packet starting at byte `i`, leaving `i` just after it; columns in errors are one-based
+fn parse_partial_packet(line: &str, i: &mut usize) -> Result<Packet, Error> {
+    let s = line.as_bytes();
+    // only ASCII is ever skipped, so `i` is always at a character boundary
+    let unexpected = |i: usize| match line[i..].chars().next() {
+        None => Error::UnexpectedEnd,
+        Some(found) => Error::Unexpected { at: i + 1, found },
+    };
+    match s.get(*i) {
+        Some(b'[') => {
+            *i += 1;
+            let mut v = vec![];
+            if s.get(*i) == Some(&b']') {
+                *i += 1;
+                return Ok(List(v));
+            }
+            loop {
+                v.push(parse_partial_packet(line, i)?);
+                match s.get(*i) {
+                    Some(b',') => *i += 1,
+                    Some(b']') => {
+                        *i += 1;
+                        return Ok(List(v));
+                    }
+                    _ => return Err(unexpected(*i)),
+                }
             }
         }
-        return Some((List(v), &s[1..]));
-    }
-    let mut n = 0;
-    while let Ok(d) = s[0..1].parse::<usize>() {
-        n = n * 10 + d;
-        s = &s[1..];
+        Some(c) if c.is_ascii_digit() => {
+            let start = *i;
+            let mut n = 0usize;
+            while let Some(&d) = s.get(*i).filter(|d| d.is_ascii_digit()) {
+                n = n
+                    .checked_mul(10)
+                    .and_then(|n| n.checked_add((d - b'0') as usize))
+                    .ok_or(Error::TooLarge { at: start + 1 })?;
+                *i += 1;
+            }
+            Ok(Integer(n))
+        }
+        _ => Err(unexpected(*i)),
     }
-    Some((Integer(n), s))
 }
 
-fn parse_packet(s: &str) -> Packet {
-    let (packet, _) = parse_partial_packet(s).unwrap();
-    packet
+impl FromStr for Packet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut i = 0;
+        let packet = parse_partial_packet(s, &mut i)?;
+        match s[i..].chars().next() {
+            None => Ok(packet),
+            Some(found) => Err(Error::Unexpected { at: i + 1, found }),
+        }
+    }
 }
 
-fn parse(input: &str) -> impl Iterator<Item = (Packet, Packet)> + '_ {
-    let lines = input.lines().collect::<Vec<_>>();
-    let n = lines.len() + 1;
-    assert_eq!(n % 3, 0);
+impl fmt::Display for Packet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Integer(n) => write!(f, "{n}"),
+            List(v) => {
+                write!(f, "[")?;
+                for (i, packet) in v.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{packet}")?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
 
-    (0..n / 3).map(move |i| {
-        let j = i * 3;
-        (parse_packet(lines[j]), parse_packet(lines[j + 1]))
-    })
+fn compare_lists(x: &[Packet], y: &[Packet]) -> Ordering {
+    x.iter()
+        .zip(y)
+        .map(|(a, b)| a.compare(b))
+        .find(|&order| order != Ordering::Equal)
+        .unwrap_or_else(|| x.len().cmp(&y.len()))
 }
 
-fn compare(left: &Packet, right: &Packet) -> Option<bool> {
+// integers before lists, and otherwise element by element
+fn compare_structure(left: &Packet, right: &Packet) -> Ordering {
     match (left, right) {
-        (Integer(x), Integer(y)) => {
-            if x == y {
-                None
-            } else {
-                Some(x < y)
-            }
-        }
-        (List(x), List(y)) => {
-            let m = x.len();
-            let n = y.len();
-            for (a, b) in x.into_iter().zip(y.into_iter()) {
-                match compare(a, b) {
-                    None => continue,
-                    Some(b) => return Some(b),
-                }
-            }
-            compare(&Integer(m), &Integer(n))
+        (Integer(x), Integer(y)) => x.cmp(y),
+        (Integer(_), List(_)) => Ordering::Less,
+        (List(_), Integer(_)) => Ordering::Greater,
+        (List(x), List(y)) => x
+            .iter()
+            .zip(y)
+            .map(|(a, b)| compare_structure(a, b))
+            .find(|&order| order != Ordering::Equal)
+            .unwrap_or_else(|| x.len().cmp(&y.len())),
+    }
+}
+
+impl Packet {
+    // the puzzle's order, where an integer compared with a list is treated as a list of just that
+    // integer, which a one-element slice of the integer itself stands in for; so `[[1]]` and `1`
+    // compare equal here
+    pub fn compare(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Integer(x), Integer(y)) => x.cmp(y),
+            (List(x), List(y)) => compare_lists(x, y),
+            (Integer(_), List(y)) => compare_lists(slice::from_ref(self), y),
+            (List(x), Integer(_)) => compare_lists(x, slice::from_ref(other)),
         }
-        (Integer(x), List(_)) => compare(&List(vec![Integer(*x)]), right),
-        (List(_), Integer(y)) => compare(left, &List(vec![Integer(*y)])),
     }
 }
 
-pub fn puzzle1(input: &str) -> usize {
-    parse(input)
+// the puzzle's order with ties broken by structure, so only identical packets are equal
+impl Ord for Packet {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.compare(other)
+            .then_with(|| compare_structure(self, other))
+    }
+}
+
+impl PartialOrd for Packet {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn parse_line(line: usize, s: &str) -> Result<Packet, Error> {
+    s.parse().map_err(|error| Error::Line {
+        line,
+        error: Box::new(error),
+    })
+}
+
+// all the packets in order, ignoring blank lines
+pub fn packets(input: &str) -> Result<Vec<Packet>, Error> {
+    input
+        .lines()
         .enumerate()
-        .filter_map(|(i, (left, right))| {
-            if let Some(true) = compare(&left, &right) {
-                Some(i + 1)
-            } else {
-                None
-            }
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| parse_line(i + 1, line))
+        .collect()
+}
+
+// pairs are two consecutive lines, separated from the other pairs by blank lines
+fn parse(input: &str) -> Result<Vec<(Packet, Packet)>, Error> {
+    let lines = input.lines().enumerate().collect::<Vec<_>>();
+    lines
+        .split(|(_, line)| line.is_empty())
+        .filter(|block| !block.is_empty())
+        .map(|block| match block {
+            [(i, left), (j, right)] => Ok((parse_line(i + 1, left)?, parse_line(j + 1, right)?)),
+            _ => Err(Error::Unpaired {
+                line: block[block.len().min(3) - 1].0 + 1,
+            }),
         })
-        .sum()
+        .collect()
+}
+
+pub fn puzzle1(input: &str) -> Result<usize, Error> {
+    Ok(parse(input)?
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (left, right))| left.compare(right) == Ordering::Less)
+        .map(|(i, _)| i + 1)
+        .sum())
 }
 
-pub fn puzzle2(input: &str) -> usize {
-    let mut packets = parse(input)
-        .flat_map(|(left, right)| [left, right])
-        .collect::<Vec<_>>();
-    let fst = List(vec![List(vec![Integer(2)])]);
-    let snd = List(vec![List(vec![Integer(6)])]);
+pub fn dividers() -> [Packet; 2] {
+    [
+        List(vec![List(vec![Integer(2)])]),
+        List(vec![List(vec![Integer(6)])]),
+    ]
+}
+
+pub fn puzzle2(input: &str) -> Result<usize, Error> {
+    let mut packets = packets(input)?;
+    let [fst, snd] = dividers();
     packets.push(fst.clone());
     packets.push(snd.clone());
-    packets.sort_by(|left, right| match compare(left, right) {
-        Some(true) => Ordering::Less,
-        None => Ordering::Equal,
-        Some(false) => Ordering::Greater,
-    });
-    (packets.iter().position(|x| *x == fst).unwrap() + 1)
-        * (packets.iter().position(|x| *x == snd).unwrap() + 1)
+    // stable, so the dividers come after any packets that compare equal to them
+    packets.sort_by(Packet::compare);
+    let position = |divider: &Packet| packets.iter().position(|p| p == divider).unwrap() + 1;
+    Ok(position(&fst) * position(&snd))
 }
 
 #[cfg(test)]
@@ -114,21 +223,88 @@ mod tests {
 
     #[test]
     fn test_puzzle1_example() {
-        assert_eq!(puzzle1(EXAMPLE), 13);
+        assert_eq!(puzzle1(EXAMPLE), Ok(13));
     }
 
     #[test]
     fn test_puzzle1_input() {
-        assert_eq!(puzzle1(INPUT), 5806);
+        assert_eq!(puzzle1(INPUT), Ok(5806));
     }
 
     #[test]
     fn test_puzzle2_example() {
-        assert_eq!(puzzle2(EXAMPLE), 140);
+        assert_eq!(puzzle2(EXAMPLE), Ok(140));
     }
 
     #[test]
     fn test_puzzle2_input() {
-        assert_eq!(puzzle2(INPUT), 23600);
+        assert_eq!(puzzle2(INPUT), Ok(23600));
+    }
+
+    #[test]
+    fn test_packet_roundtrip() {
+        for line in EXAMPLE.lines().filter(|line| !line.is_empty()) {
+            assert_eq!(line.parse::<Packet>().unwrap().to_string(), line);
+        }
+        assert_eq!(
+            "[1,,2]".parse::<Packet>(),
+            Err(Error::Unexpected { at: 4, found: ',' })
+        );
+        assert_eq!("[1,[2]".parse::<Packet>(), Err(Error::UnexpectedEnd));
+        assert_eq!(
+            "[] x".parse::<Packet>(),
+            Err(Error::Unexpected { at: 3, found: ' ' })
+        );
+        assert_eq!("".parse::<Packet>(), Err(Error::UnexpectedEnd));
+        assert_eq!(
+            "[99999999999999999999]".parse::<Packet>(),
+            Err(Error::TooLarge { at: 2 }),
+        );
+        assert_eq!(
+            puzzle1("[1]\n[2]\n\n[3]\n\n\n"),
+            Err(Error::Unpaired { line: 4 }),
+        );
+        assert_eq!(
+            puzzle1("[1]\n[2]\n[3]\n[4]\n"),
+            Err(Error::Unpaired { line: 3 }),
+        );
+        assert_eq!(
+            puzzle2("[1]\n[é]\n"),
+            Err(Error::Line {
+                line: 2,
+                error: Box::new(Error::Unexpected { at: 2, found: 'é' }),
+            }),
+        );
+    }
+
+    #[test]
+    fn test_packet_order() {
+        let p = |s: &str| s.parse::<Packet>().unwrap();
+        assert!(p("[1,1,3,1,1]") < p("[1,1,5,1,1]"));
+        assert!(p("[[1],[2,3,4]]") < p("[[1],4]"));
+        assert!(p("[9]") > p("[[8,7,6]]"));
+        assert!(p("[]") < p("[3]"));
+        assert_eq!(p("[[[1]]]").compare(&p("1")), Ordering::Equal);
+        assert_ne!(p("[[[1]]]"), p("1"));
+        assert!(p("1") < p("[1]") && p("[1]") < p("[[1]]"));
+        let set = packets(EXAMPLE)
+            .unwrap()
+            .into_iter()
+            .chain(dividers())
+            .collect::<std::collections::BTreeSet<_>>();
+        let sorted = set.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        assert_eq!(sorted[0], "[]");
+        assert_eq!(sorted[9], "[[2]]");
+        assert_eq!(sorted.last().unwrap(), "[9]");
+        assert_eq!(
+            packets("[1]\n[[1]]\n")
+                .unwrap()
+                .into_iter()
+                .collect::<std::collections::BTreeSet<_>>()
+                .len(),
+            2,
+        );
+        // a packet equal to the first divider is sorted before it
+        assert_eq!(puzzle2("[2]\n2\n"), Ok(3 * 4));
     }
 }
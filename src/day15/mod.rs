@@ -2,6 +2,8 @@ use std::collections::HashSet;
 
 use regex::Regex;
 
+use crate::interval::{self, Interval};
+
 fn parse(input: &str) -> impl Iterator<Item = ((isize, isize), (isize, isize))> + '_ {
     let re =
         Regex::new(r"^Sensor at x=(.+), y=(.+): closest beacon is at x=(.+), y=(.+)$").unwrap();
@@ -16,22 +18,26 @@ fn parse(input: &str) -> impl Iterator<Item = ((isize, isize), (isize, isize))>
 
 pub fn puzzle1(input: &str, row: isize) -> usize {
     let mut beacons = HashSet::new();
-    let mut coverage = HashSet::new();
+    let mut coverage = vec![];
     for (sensor, beacon) in parse(input) {
-        beacons.insert(beacon);
+        if beacon.1 == row {
+            beacons.insert(beacon.0);
+        }
         let d = (beacon.0 - sensor.0).abs() + (beacon.1 - sensor.1).abs();
         let h = (row - sensor.1).abs();
         let w = d - h;
-        for x in sensor.0 - w..=sensor.0 + w {
-            coverage.insert(x);
+        if w >= 0 {
+            coverage.push(Interval::new(sensor.0 - w, sensor.0 + w));
         }
     }
-    for (x, y) in beacons {
-        if y == row {
-            coverage.remove(&x);
-        }
+    let mut coverage = interval::merge(coverage);
+    for x in beacons {
+        coverage = coverage
+            .into_iter()
+            .flat_map(|i| i.difference(Interval::point(x)))
+            .collect();
     }
-    coverage.len()
+    coverage.into_iter().map(Interval::len).sum()
 }
 
 fn rect_to_diag(x: isize, y: isize) -> (isize, isize) {
@@ -0,0 +1,156 @@
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Interval {
+    pub start: isize,
+    pub end: isize,
+}
+
+impl Interval {
+    pub fn new(start: isize, end: isize) -> Self {
+        assert!(start <= end, "empty interval {}-{}", start, end);
+        Interval { start, end }
+    }
+
+    pub fn point(x: isize) -> Self {
+        Interval { start: x, end: x }
+    }
+
+    pub fn len(self) -> usize {
+        (self.end - self.start + 1) as usize
+    }
+
+    pub fn contains_point(self, x: isize) -> bool {
+        self.start <= x && x <= self.end
+    }
+
+    pub fn contains(self, other: Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    pub fn intersection(self, other: Interval) -> Option<Interval> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        if start <= end {
+            Some(Interval { start, end })
+        } else {
+            None
+        }
+    }
+
+    pub fn overlaps(self, other: Interval) -> bool {
+        self.intersection(other).is_some()
+    }
+
+    // `None` if there is a gap between the two, since then the union isn't an interval
+    pub fn union(self, other: Interval) -> Option<Interval> {
+        if self.end + 1 < other.start || other.end + 1 < self.start {
+            None
+        } else {
+            Some(Interval {
+                start: self.start.min(other.start),
+                end: self.end.max(other.end),
+            })
+        }
+    }
+
+    pub fn difference(self, other: Interval) -> Vec<Interval> {
+        match self.intersection(other) {
+            None => vec![self],
+            Some(middle) => {
+                let mut pieces = vec![];
+                if self.start < middle.start {
+                    pieces.push(Interval::new(self.start, middle.start - 1));
+                }
+                if middle.end < self.end {
+                    pieces.push(Interval::new(middle.end + 1, self.end));
+                }
+                pieces
+            }
+        }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// sorted, disjoint and non-adjacent
+pub fn merge(intervals: impl IntoIterator<Item = Interval>) -> Vec<Interval> {
+    let mut sorted = intervals.into_iter().collect::<Vec<_>>();
+    sorted.sort();
+    let mut merged: Vec<Interval> = vec![];
+    for interval in sorted {
+        match merged.last_mut() {
+            Some(last) => match last.union(interval) {
+                Some(union) => *last = union,
+                None => merged.push(interval),
+            },
+            None => merged.push(interval),
+        }
+    }
+    merged
+}
+
+// sorted disjoint pieces, each with the number of given intervals covering it; uncovered gaps are
+// omitted
+pub fn coverage(intervals: impl IntoIterator<Item = Interval>) -> Vec<(Interval, usize)> {
+    let mut events = vec![];
+    for Interval { start, end } in intervals {
+        events.push((start, 1));
+        events.push((end + 1, -1));
+    }
+    events.sort();
+    let mut pieces: Vec<(Interval, usize)> = vec![];
+    let mut count: isize = 0;
+    let mut i = 0;
+    while i < events.len() {
+        let x = events[i].0;
+        while i < events.len() && events[i].0 == x {
+            count += events[i].1;
+            i += 1;
+        }
+        if count > 0 {
+            let piece = Interval::new(x, events[i].0 - 1);
+            match pieces.last_mut() {
+                Some((last, n)) if *n == count as usize && last.end + 1 == x => {
+                    last.end = piece.end
+                }
+                _ => pieces.push((piece, count as usize)),
+            }
+        }
+    }
+    pieces
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difference() {
+        let a = Interval::new(2, 8);
+        assert_eq!(a.difference(Interval::new(4, 5)), [(2, 3), (6, 8)].map(i));
+        assert_eq!(a.difference(Interval::new(0, 5)), [(6, 8)].map(i));
+        assert_eq!(a.difference(Interval::new(0, 9)), []);
+        assert_eq!(a.difference(Interval::new(9, 9)), [a]);
+    }
+
+    #[test]
+    fn test_merge() {
+        let merged = merge([(6, 8), (1, 2), (3, 4), (7, 10), (12, 12)].map(i));
+        assert_eq!(merged, [(1, 4), (6, 10), (12, 12)].map(i));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let pieces = coverage([(2, 4), (6, 8), (3, 7), (5, 5)].map(i));
+        assert_eq!(pieces, [(i((2, 2)), 1), (i((3, 7)), 2), (i((8, 8)), 1)]);
+    }
+
+    fn i((start, end): (isize, isize)) -> Interval {
+        Interval::new(start, end)
+    }
+}
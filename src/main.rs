@@ -24,11 +24,435 @@ mod day23;
 mod day24;
 mod day25;
 
-use std::{env, fs};
+mod interval;
+
+use itertools::Itertools;
+use std::{
+    collections::BTreeSet,
+    env, fs,
+    io::{self, BufRead},
+    path::Path,
+};
+
+fn read(args: &mut impl Iterator<Item = String>) -> String {
+    fs::read_to_string(args.next().unwrap()).unwrap()
+}
+
+// every simulated round, then totals for all the rounds, extrapolated if they started repeating
+fn monkey_history<S: day11::Strategy>(monkeys: &[day11::Monkey], rounds: usize, strategy: &S) {
+    let mut simulation = day11::Simulation::new(monkeys, strategy);
+    let totals = simulation
+        .totals(rounds)
+        .unwrap_or_else(|err| panic!("{}", err));
+    for (round, inspections) in simulation.history().iter().enumerate() {
+        println!(
+            "round {}: inspections {}",
+            round + 1,
+            inspections.iter().join(" ")
+        );
+        for (i, held) in simulation.holdings(round + 1).iter().enumerate() {
+            let items = held.iter().map(|(item, w)| format!("#{item}={w}"));
+            println!("  monkey {i}: {}", items.format(", "));
+        }
+    }
+    let items = simulation.holdings(0).iter().flatten().count();
+    for item in 0..items {
+        println!(
+            "item {item}: {}",
+            simulation.journey(item).iter().join(" -> ")
+        );
+    }
+    if let Some((start, period)) = simulation.cycle() {
+        println!("repeats every {period} rounds after round {start}");
+    }
+    println!("after {rounds} rounds: {}", totals.iter().join(" "));
+    println!("monkey business: {}", day11::business(&totals));
+}
+
+fn command(name: &str, mut args: impl Iterator<Item = String>) {
+    match name {
+        "rucksacks" => {
+            let input = read(&mut args);
+            let reports = match args.next() {
+                Some(size) => day03::groups(&input, size.parse().unwrap()),
+                None => day03::rucksacks(&input),
+            };
+            for report in reports {
+                match report {
+                    Ok(report) => println!("{}", report),
+                    Err(err) => println!("error: {}", err),
+                }
+            }
+        }
+
+        "coverage" => {
+            let input = read(&mut args);
+            for (sections, count) in day04::coverage(&input) {
+                println!("{}: {}", sections, count);
+            }
+        }
+
+        "roster" => {
+            let input = read(&mut args);
+            let roster = day04::roster(&input);
+            for line in io::stdin().lock().lines() {
+                let line = line.unwrap();
+                let mut words = line.split_whitespace();
+                match (words.next(), words.next()) {
+                    (Some("covering"), Some(section)) => match section.parse() {
+                        Ok(section) => println!("{:?}", roster.covering(section)),
+                        Err(err) => println!("error: {}", err),
+                    },
+                    (Some("overlapping"), None) => println!("{:?}", roster.overlapping()),
+                    (Some("containing"), None) => println!("{:?}", roster.containing()),
+                    (Some("uncovered"), None) => {
+                        let gaps = roster.uncovered();
+                        println!("{}", gaps.iter().map(|gap| gap.to_string()).join(","));
+                    }
+                    (None, _) => {}
+                    _ => {
+                        println!("queries: covering <section>, overlapping, containing, uncovered")
+                    }
+                }
+            }
+        }
+
+        "crates" => {
+            let input = read(&mut args);
+            let crane = day05::crane(&args.next().unwrap_or_else(|| "9000".to_string())).unwrap();
+            for stacks in day05::replay(&input, crane.as_ref()).unwrap() {
+                match stacks {
+                    Ok(stacks) => println!("{}", stacks),
+                    Err(err) => println!("error: {}", err),
+                }
+            }
+        }
+
+        "plan" => {
+            let input = read(&mut args);
+            let target = read(&mut args);
+            let crane = day05::crane(&args.next().unwrap_or_else(|| "9000".to_string())).unwrap();
+            let limit = args.next().map_or(100_000, |limit| limit.parse().unwrap());
+            let plan = day05::stacks(&input).and_then(|start| {
+                let moves = day05::plan(&start, &day05::stacks(&target)?, crane.as_ref(), limit)?;
+                Ok(day05::instructions(&start, &moves))
+            });
+            match plan {
+                Ok(plan) => print!("{}", plan),
+                Err(err) => println!("error: {}", err),
+            }
+        }
+
+        "markers" => {
+            let input = read(&mut args);
+            for i in day06::markers(
+                input.trim_end().as_bytes(),
+                args.next().unwrap().parse().unwrap(),
+            ) {
+                println!("{}", i.unwrap());
+            }
+        }
+
+        "decode" => {
+            let mut lengths = args.map(|n| n.parse().unwrap());
+            let packet = lengths.next().unwrap_or(4);
+            let message = lengths.next().unwrap_or(14);
+            let max_len = lengths.next().unwrap_or(1 << 16);
+            for frame in day06::decode(io::stdin(), packet, message, max_len) {
+                println!("{}", frame.unwrap());
+            }
+        }
+
+        "find" => {
+            let fs = day07::filesystem(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let mut args = args.peekable();
+            let root = match args.next_if(|arg| !arg.starts_with('-')) {
+                Some(path) => fs.lookup(&path).unwrap(),
+                None => 0,
+            };
+            let mut query = day07::Query::default();
+            while let Some(flag) = args.next() {
+                let value = args.next().unwrap();
+                match flag.as_str() {
+                    "-type" => match value.as_str() {
+                        "d" => query.dirs = true,
+                        "f" => query.files = true,
+                        _ => panic!("bad type {}", value),
+                    },
+                    "-min" => query.min = Some(value.parse().unwrap()),
+                    "-max" => query.max = Some(value.parse().unwrap()),
+                    "-name" => query.name = Some(value),
+                    _ => panic!("bad flag {}", flag),
+                }
+            }
+            if !query.dirs && !query.files {
+                query.dirs = true;
+                query.files = true;
+            }
+            for id in fs.find(root, &query) {
+                println!("{} {}", fs.node(id).size, fs.path(id));
+            }
+        }
+
+        "tree" | "du" | "json" => {
+            let fs = day07::filesystem(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let root = args.next().map_or(0, |path| fs.lookup(&path).unwrap());
+            match name {
+                "tree" => print!("{}", fs.tree(root)),
+                "du" => print!("{}", fs.du(root)),
+                _ => println!("{}", fs.json(root)),
+            }
+        }
+
+        "delete" => {
+            let fs = day07::filesystem(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let day07::Deletion {
+                used,
+                need,
+                candidates,
+            } = day07::deletion(&fs);
+            println!("used {} of 70000000, so need to free {}", used, need);
+            for (i, &id) in candidates.iter().enumerate() {
+                let chosen = if i == 0 { " (chosen)" } else { "" };
+                println!("{} {}{}", fs.node(id).size, fs.path(id), chosen);
+            }
+        }
+
+        "scan" => {
+            let fs = day07::Fs::scan(Path::new(&args.next().unwrap())).unwrap();
+            print!("{}", fs.transcript());
+        }
+
+        "heatmap" => {
+            let grid = day08::parse(&read(&mut args));
+            let mode = day08::Mode::parse(&args.next().unwrap()).unwrap();
+            match args.next().as_deref() {
+                Some("ppm") => print!("{}", day08::ppm(&grid, mode)),
+                _ => print!("{}", day08::heatmap(&grid, mode)),
+            }
+        }
+
+        "rope" => {
+            let input = read(&mut args);
+            let n = args.next().unwrap().parse().unwrap();
+            let knot = args.next().map_or(n - 1, |knot| knot.parse().unwrap());
+            let rope = day09::simulate(&input, n);
+            let Some(&day09::Pos { x, y }) = rope.knots().get(knot) else {
+                panic!("no knot {} in a rope of {}", knot, n);
+            };
+            println!("knot {} ended at {},{}", knot, x, y);
+            println!("and visited {} positions", rope.visited(knot).len());
+        }
+
+        "trail" => {
+            let input = read(&mut args);
+            let n = args.next().unwrap().parse().unwrap();
+            let knot = args.next().map_or(n - 1, |knot| knot.parse().unwrap());
+            print!(
+                "{}",
+                day09::render_visited(day09::simulate(&input, n).visited(knot))
+            );
+        }
+
+        "animate" | "knots-csv" => {
+            let trace = day09::trace(&read(&mut args), args.next().unwrap().parse().unwrap());
+            if name == "knots-csv" {
+                print!("{}", day09::csv(&trace));
+            } else {
+                for (step, frame) in day09::animate(&trace).into_iter().enumerate() {
+                    println!("== step {} ==\n{}", step, frame);
+                }
+            }
+        }
+
+        "debug" => {
+            let program = day10::parse(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let w = args.next().map_or(40, |w| w.parse().unwrap());
+            let h = args.next().map_or(6, |h| h.parse().unwrap());
+            let mut debugger = day10::Debugger::new(program, w, h);
+            println!("{}", debugger.status());
+            for line in io::stdin().lock().lines() {
+                let line = line.unwrap();
+                let words = line.split_whitespace().collect::<Vec<_>>();
+                let count = |i: usize| words.get(i).map_or(1, |n| n.parse().unwrap());
+                let stop = match words.as_slice() {
+                    ["step" | "s", ..] => (0..count(1)).find_map(|_| debugger.step()),
+                    ["back" | "b", ..] => (0..count(1)).find_map(|_| debugger.back()),
+                    ["continue" | "c"] => Some(debugger.cont()),
+                    ["break", "cycle", n] => {
+                        debugger
+                            .breakpoints
+                            .push(day10::Break::Cycle(n.parse().unwrap()));
+                        continue;
+                    }
+                    ["break", "instr", n] => {
+                        debugger
+                            .breakpoints
+                            .push(day10::Break::Instr(n.parse().unwrap()));
+                        continue;
+                    }
+                    ["delete"] => {
+                        debugger.breakpoints.clear();
+                        continue;
+                    }
+                    ["watch"] | ["unwatch"] => {
+                        debugger.watch = words[0] == "watch";
+                        continue;
+                    }
+                    ["crt"] => {
+                        print!("{}", debugger.screen());
+                        continue;
+                    }
+                    ["quit" | "q"] => break,
+                    _ => {
+                        println!("commands: step [n], back [n], continue, break cycle <n>, break instr <i>, delete, watch, unwatch, crt, quit");
+                        continue;
+                    }
+                };
+                match stop {
+                    Some(day10::Stop::Halted) => println!("halted"),
+                    Some(day10::Stop::Fault(err)) => println!("error: {}", err),
+                    Some(day10::Stop::Break) => println!("breakpoint"),
+                    Some(day10::Stop::Watch { old, new }) => {
+                        println!("x changed from {} to {}", old, new)
+                    }
+                    Some(day10::Stop::Start) => println!("at the start"),
+                    Some(day10::Stop::Forgotten) => println!("no more history"),
+                    Some(day10::Stop::Limit) => println!("cycle limit reached"),
+                    None => {}
+                }
+                println!("{}", debugger.status());
+            }
+        }
+
+        "crt" => {
+            let program = day10::parse(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let w = args.next().unwrap().parse().unwrap();
+            let h = args.next().unwrap().parse().unwrap();
+            print!(
+                "{}",
+                day10::draw(&program, w, h).unwrap_or_else(|err| panic!("{}", err))
+            );
+        }
+
+        "compile" => {
+            let image = match args.next().unwrap().as_str() {
+                "--text" => {
+                    let text = args.next().unwrap();
+                    let w = args.next().map_or(40, |w| w.parse().unwrap());
+                    let h = args.next().map_or(6, |h| h.parse().unwrap());
+                    day10::typeset(&text, w, h).expect("unknown letter or too small")
+                }
+                path => day10::bitmap(&fs::read_to_string(path).unwrap())
+                    .unwrap_or_else(|err| panic!("{}", err)),
+            };
+            let program = day10::compile(&image).unwrap_or_else(|err| panic!("{}", err));
+            let w = image.first().map_or(0, |row| row.len());
+            let drawn = day10::draw(&program, w, image.len()).unwrap();
+            assert_eq!(day10::bitmap(&drawn), Ok(image), "round trip failed");
+            print!("{}", day10::disassemble(&program));
+        }
+
+        "monkeys" => {
+            let monkeys = day11::parse(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let rounds = args.next().unwrap().parse().unwrap();
+            let strategy = args.next().unwrap();
+            let mut k = || args.next().map_or(1, |k| k.parse().unwrap());
+            let inspections = match strategy.as_str() {
+                "divide" => day11::Checked::new(k())
+                    .and_then(|checked| day11::inspections(&monkeys, rounds, &checked)),
+                "checked" => day11::Checked::new(1)
+                    .and_then(|checked| day11::inspections(&monkeys, rounds, &checked)),
+                "lcm" => day11::Modular::lcm(&monkeys)
+                    .and_then(|modular| day11::inspections(&monkeys, rounds, &modular)),
+                "big" => {
+                    day11::Big::new(k()).and_then(|big| day11::inspections(&monkeys, rounds, &big))
+                }
+                _ => panic!("strategies: divide <k>, checked, lcm, big [k]"),
+            }
+            .unwrap_or_else(|err| panic!("{}", err));
+            for (i, n) in inspections.iter().enumerate() {
+                println!("monkey {i}: {n}");
+            }
+            println!("monkey business: {}", day11::business(&inspections));
+        }
+
+        "monkey-history" => {
+            let monkeys = day11::parse(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let rounds = args.next().unwrap().parse().unwrap();
+            match args.next().map(|k| k.parse().unwrap()) {
+                Some(divide) => {
+                    let checked =
+                        day11::Checked::new(divide).unwrap_or_else(|err| panic!("{}", err));
+                    monkey_history(&monkeys, rounds, &checked)
+                }
+                None => {
+                    let modular =
+                        day11::Modular::lcm(&monkeys).unwrap_or_else(|err| panic!("{}", err));
+                    monkey_history(&monkeys, rounds, &modular)
+                }
+            }
+        }
+
+        "climb" => {
+            let map = day12::parse(&read(&mut args));
+            let mut rules = day12::Rules::default();
+            let (mut lowest, mut heights) = (false, false);
+            while let Some(arg) = args.next() {
+                let mut value = || args.next().unwrap();
+                match arg.as_str() {
+                    "--lowest" => lowest = true,
+                    "--heights" => heights = true,
+                    "--diagonal" => rules.diagonal = true,
+                    "--up" => rules.up = value().parse().unwrap(),
+                    "--down" => rules.down = value().parse().unwrap(),
+                    // like `--cost z=5`
+                    "--cost" => {
+                        let value = value();
+                        let (height, cost) = value.split_once('=').unwrap();
+                        let height = day12::parse_char(height.parse().unwrap()) as usize;
+                        rules.costs[height] = cost.parse().unwrap();
+                    }
+                    _ => panic!("unknown option {}", arg),
+                }
+            }
+            let route = if lowest {
+                day12::Field::new(&map, &rules).best(&day12::lowest(&map))
+            } else {
+                day12::route(&map, &rules, &[map.start()])
+            }
+            .expect("no route to E");
+            let (y, x) = route.start();
+            println!(
+                "{} steps costing {} from row {y} column {x}",
+                route.steps(),
+                route.cost
+            );
+            print!("{}", day12::render(&map, &route, heights));
+        }
+
+        "packets" => {
+            let packets = day13::packets(&read(&mut args)).unwrap_or_else(|err| panic!("{}", err));
+            let sorted = packets
+                .into_iter()
+                .chain(day13::dividers())
+                .collect::<BTreeSet<_>>();
+            for packet in sorted {
+                println!("{}", packet);
+            }
+        }
+
+        _ => panic!("no command {}", name),
+    }
+}
 
 fn main() {
     let mut args = env::args().skip(1);
-    let puzzle = args.next().unwrap().parse().unwrap();
+    let first = args.next().unwrap();
+    let puzzle = match first.parse() {
+        Ok(puzzle) => puzzle,
+        Err(_) => return command(&first, args),
+    };
     let day = args.next().unwrap().parse().unwrap();
     let input = fs::read_to_string(args.next().unwrap()).unwrap();
     let answer = match (puzzle, day) {
@@ -44,14 +468,18 @@ fn main() {
         (4, 1) => day04::puzzle1(&input).to_string(),
         (4, 2) => day04::puzzle2(&input).to_string(),
 
-        (5, 1) => day05::puzzle1(&input),
-        (5, 2) => day05::puzzle2(&input),
+        (5, 1) => day05::puzzle1(&input).unwrap_or_else(|err| panic!("{}", err)),
+        (5, 2) => day05::puzzle2(&input).unwrap_or_else(|err| panic!("{}", err)),
 
         (6, 1) => day06::puzzle1(&input).to_string(),
         (6, 2) => day06::puzzle2(&input).to_string(),
 
-        (7, 1) => day07::puzzle1(&input).to_string(),
-        (7, 2) => day07::puzzle2(&input).to_string(),
+        (7, 1) => day07::puzzle1(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
+        (7, 2) => day07::puzzle2(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
 
         (8, 1) => day08::puzzle1(&input).to_string(),
         (8, 2) => day08::puzzle2(&input).to_string(),
@@ -59,17 +487,27 @@ fn main() {
         (9, 1) => day09::puzzle1(&input).to_string(),
         (9, 2) => day09::puzzle2(&input).to_string(),
 
-        (10, 1) => day10::puzzle1(&input).to_string(),
-        (10, 2) => day10::puzzle2(&input),
+        (10, 1) => day10::puzzle1(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
+        (10, 2) => day10::puzzle2(&input).unwrap_or_else(|err| panic!("{}", err)),
 
-        (11, 1) => day11::puzzle1(&input).to_string(),
-        (11, 2) => day11::puzzle2(&input).to_string(),
+        (11, 1) => day11::puzzle1(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
+        (11, 2) => day11::puzzle2(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
 
         (12, 1) => day12::puzzle1(&input).to_string(),
         (12, 2) => day12::puzzle2(&input).to_string(),
 
-        (13, 1) => day13::puzzle1(&input).to_string(),
-        (13, 2) => day13::puzzle2(&input).to_string(),
+        (13, 1) => day13::puzzle1(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
+        (13, 2) => day13::puzzle2(&input)
+            .unwrap_or_else(|err| panic!("{}", err))
+            .to_string(),
 
         (14, 1) => day14::puzzle1(&input).to_string(),
         (14, 2) => day14::puzzle2(&input).to_string(),